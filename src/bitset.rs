@@ -0,0 +1,197 @@
+use std::iter;
+
+pub const BLOCK_BITS: usize = 64;
+
+/// A fixed-size set of small indices, stored as one bit per index.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Bitset {
+    pub(crate) blocks: Vec<u64>,
+}
+
+impl Bitset {
+    /// An empty set with room for the indices `0..len`.
+    #[must_use]
+    pub fn new(len: usize) -> Self {
+        Self {
+            blocks: vec![0; len.div_ceil(BLOCK_BITS)],
+        }
+    }
+
+    /// A set containing every index in `0..len`.
+    #[must_use]
+    pub fn full(len: usize) -> Self {
+        let mut bitset = Self::new(len);
+        for index in 0..len {
+            bitset.insert(index);
+        }
+        bitset
+    }
+
+    /// A set containing only `index`.
+    #[must_use]
+    pub fn single(len: usize, index: usize) -> Self {
+        let mut bitset = Self::new(len);
+        bitset.insert(index);
+        bitset
+    }
+
+    fn get_mask(index: usize) -> u64 {
+        1_u64
+            .checked_shl(u32::try_from(index % BLOCK_BITS).unwrap_or(0))
+            .unwrap_or(0)
+    }
+
+    /// Indices past the end of the set are ignored.
+    pub fn insert(&mut self, index: usize) {
+        if let Some(block) = self.blocks.get_mut(index / BLOCK_BITS) {
+            *block |= Self::get_mask(index);
+        }
+    }
+
+    /// Returns whether `index` was in the set.
+    pub fn remove(&mut self, index: usize) -> bool {
+        let Some(block) = self.blocks.get_mut(index / BLOCK_BITS) else {
+            return false;
+        };
+
+        let was_set = *block & Self::get_mask(index) != 0;
+        *block &= !Self::get_mask(index);
+        was_set
+    }
+
+    /// Whether `index` is in the set.
+    #[must_use]
+    pub fn contains(&self, index: usize) -> bool {
+        self.blocks
+            .get(index / BLOCK_BITS)
+            .is_some_and(|block| block & Self::get_mask(index) != 0)
+    }
+
+    /// The number of indices in the set.
+    #[must_use]
+    pub fn count(&self) -> u32 {
+        self.blocks.iter().map(|block| block.count_ones()).sum()
+    }
+
+    /// Whether the set contains no indices.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|block| *block == 0)
+    }
+
+    /// Adds every index in `other`.
+    pub fn union_with(&mut self, other: &Self) {
+        for (block, other_block) in self.blocks.iter_mut().zip(&other.blocks) {
+            *block |= *other_block;
+        }
+    }
+
+    /// Keeps only the indices that are also in `other`.
+    pub fn intersect_with(&mut self, other: &Self) {
+        for (index, block) in self.blocks.iter_mut().enumerate() {
+            *block &= other.blocks.get(index).copied().unwrap_or(0);
+        }
+    }
+
+    /// The indices in this set that are not in `other`.
+    #[must_use]
+    pub fn difference(&self, other: &Self) -> Self {
+        Self {
+            blocks: self
+                .blocks
+                .iter()
+                .enumerate()
+                .map(|(index, block)| *block & !other.blocks.get(index).copied().unwrap_or(0))
+                .collect(),
+        }
+    }
+
+    /// The indices in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(block_index, block)| {
+                let mut bits = *block;
+                iter::from_fn(move || {
+                    if bits == 0 {
+                        return None;
+                    }
+
+                    let bit = usize::try_from(bits.trailing_zeros()).ok()?;
+                    bits &= bits.wrapping_sub(1);
+                    block_index.checked_mul(BLOCK_BITS)?.checked_add(bit)
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_and_removes_across_blocks() {
+        let mut bitset = Bitset::new(130);
+        assert!(bitset.is_empty());
+        for index in [0, 63, 64, 129] {
+            bitset.insert(index);
+        }
+        assert_eq!(bitset.count(), 4);
+        assert!(bitset.contains(64));
+        assert!(!bitset.contains(65));
+
+        assert!(bitset.remove(64));
+        assert!(!bitset.remove(64));
+        assert!(!bitset.contains(64));
+        assert_eq!(bitset.iter().collect::<Vec<_>>(), [0, 63, 129]);
+    }
+
+    #[test]
+    fn ignores_indices_past_the_end() {
+        let mut bitset = Bitset::new(10);
+        bitset.insert(200);
+        assert!(bitset.is_empty());
+        assert!(!bitset.contains(200));
+        assert!(!bitset.remove(200));
+    }
+
+    #[test]
+    fn full_and_single() {
+        assert_eq!(
+            Bitset::full(70).iter().collect::<Vec<_>>(),
+            (0..70).collect::<Vec<_>>()
+        );
+        assert_eq!(Bitset::full(0).count(), 0);
+        assert_eq!(Bitset::single(70, 65).iter().collect::<Vec<_>>(), [65]);
+    }
+
+    #[test]
+    fn set_operations() {
+        let evens = (0..100)
+            .step_by(2)
+            .fold(Bitset::new(100), |mut bitset, index| {
+                bitset.insert(index);
+                bitset
+            });
+        let low = (0..50).fold(Bitset::new(100), |mut bitset, index| {
+            bitset.insert(index);
+            bitset
+        });
+
+        let mut union = evens.clone();
+        union.union_with(&low);
+        assert_eq!(union.count(), 75);
+
+        let mut intersection = evens.clone();
+        intersection.intersect_with(&low);
+        assert_eq!(intersection.count(), 25);
+        assert!(intersection
+            .iter()
+            .all(|index| index < 50 && index % 2 == 0));
+
+        let difference = evens.difference(&low);
+        assert_eq!(difference.count(), 25);
+        assert!(difference.iter().all(|index| index >= 50 && index % 2 == 0));
+    }
+}
@@ -0,0 +1,496 @@
+use crate::bitset::Bitset;
+use crate::direction::{Direction, Neighbourhood};
+use core::fmt;
+
+/// The tiles a hidden cell may still become, with the running sums its entropy is computed from.
+#[derive(Clone, Debug)]
+pub struct PossibleTiles {
+    pub(crate) choices: Bitset,
+    pub(crate) weight_sum: f64,
+    pub(crate) weight_log_weight_sum: f64,
+    /// choices with a zero weight only matter once nothing else is left
+    pub(crate) positive_choices: usize,
+    pub(crate) entropy: f64,
+}
+
+pub fn get_weight(weights: &[f64], tile: usize) -> f64 {
+    weights.get(tile).copied().unwrap_or(0.0)
+}
+
+pub fn weight_log_weight(weight: f64) -> f64 {
+    if weight > 0.0 {
+        weight * weight.ln()
+    } else {
+        0.0
+    }
+}
+
+impl PossibleTiles {
+    #[must_use]
+    pub(crate) fn new(choices: Bitset, weights: &[f64]) -> Self {
+        let mut possible_tiles = Self {
+            choices,
+            weight_sum: 0.0,
+            weight_log_weight_sum: 0.0,
+            positive_choices: 0,
+            entropy: 0.0,
+        };
+
+        for tile in possible_tiles.choices.iter() {
+            let weight = get_weight(weights, tile);
+            if weight > 0.0 {
+                possible_tiles.weight_sum += weight;
+                possible_tiles.weight_log_weight_sum += weight_log_weight(weight);
+                possible_tiles.positive_choices = possible_tiles.positive_choices.saturating_add(1);
+            }
+        }
+        possible_tiles.update_entropy();
+        possible_tiles
+    }
+
+    pub(crate) fn remove(&mut self, tile: usize, weights: &[f64]) {
+        if self.choices.remove(tile) {
+            self.subtract_weight(tile, weights);
+            self.update_entropy();
+        }
+    }
+
+    /// Gives a removed choice back, as when a decision is undone.
+    pub(crate) fn insert(&mut self, tile: usize, weights: &[f64]) {
+        if !self.choices.contains(tile) {
+            self.choices.insert(tile);
+            self.add_weight(tile, weights);
+            self.update_entropy();
+        }
+    }
+
+    /// Keeps only the choices in `allowed`, returning the ones that were removed.
+    pub(crate) fn restrict(&mut self, allowed: &Bitset, weights: &[f64]) -> Bitset {
+        let removed = self.choices.difference(allowed);
+        if removed.is_empty() {
+            return removed;
+        }
+
+        self.choices.intersect_with(allowed);
+        for tile in removed.iter() {
+            self.subtract_weight(tile, weights);
+        }
+        self.update_entropy();
+        removed
+    }
+
+    /// The indices of the tiles still allowed in the cell.
+    #[must_use]
+    pub const fn choices(&self) -> &Bitset {
+        &self.choices
+    }
+
+    /// The Shannon entropy of the weighted choices, lowest for the cells closest to being decided.
+    #[must_use]
+    pub const fn entropy(&self) -> f64 {
+        self.entropy
+    }
+
+    fn add_weight(&mut self, tile: usize, weights: &[f64]) {
+        let weight = get_weight(weights, tile);
+        if weight > 0.0 {
+            self.weight_sum += weight;
+            self.weight_log_weight_sum += weight_log_weight(weight);
+            self.positive_choices = self.positive_choices.saturating_add(1);
+        }
+    }
+
+    fn subtract_weight(&mut self, tile: usize, weights: &[f64]) {
+        let weight = get_weight(weights, tile);
+        if weight > 0.0 {
+            self.weight_sum -= weight;
+            self.weight_log_weight_sum -= weight_log_weight(weight);
+            self.positive_choices = self.positive_choices.saturating_sub(1);
+        }
+    }
+
+    // Shannon entropy of the weighted choices, H = ln(W) - sum(w * ln(w)) / W. Once only
+    // zero-weight choices remain they are treated as equally likely, matching `choose_tile`.
+    fn update_entropy(&mut self) {
+        self.entropy = if self.positive_choices > 0 && self.weight_sum > 0.0 {
+            self.weight_sum.ln() - self.weight_log_weight_sum / self.weight_sum
+        } else {
+            f64::from(self.choices.count().max(1)).ln()
+        };
+    }
+}
+
+/// Cells refer to tiles by their index in `Generation::tiles`.
+#[derive(Clone, Debug)]
+pub enum Tile {
+    /// The cell has been collapsed to a single tile.
+    Revealed(usize),
+    /// The cell could still become any of several tiles.
+    Hidden(PossibleTiles),
+}
+
+/// The coordinates of a cell that was left with no possible tiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Contradiction {
+    /// Column of the cell.
+    pub width: usize,
+    /// Row of the cell.
+    pub height: usize,
+    /// Layer of the cell, or `None` on a board with a single layer.
+    pub depth: Option<usize>,
+}
+
+impl fmt::Display for Contradiction {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.depth {
+            Some(depth) => write!(formatter, "({}, {}, {depth})", self.width, self.height),
+            None => write!(formatter, "({}, {})", self.width, self.height),
+        }
+    }
+}
+
+/// Which axes of the board wrap around, so that cells on opposite edges are neighbours.
+///
+/// Hex boards only line up across the top and bottom edges if they have an even number of rows,
+/// so the solver refuses to wrap them vertically otherwise.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Wrapping {
+    /// The left and right edges meet.
+    pub horizontal: bool,
+    /// The top and bottom edges meet.
+    pub vertical: bool,
+}
+
+/// The size of a board. Flat boards have a depth of one.
+#[derive(Clone, Copy, Debug)]
+pub struct Dimensions {
+    /// Number of columns.
+    pub width: usize,
+    /// Number of rows.
+    pub height: usize,
+    /// Number of layers.
+    pub depth: usize,
+}
+
+/// A grid of cells, each either revealed or still holding its possible tiles.
+#[derive(Clone, Debug)]
+pub struct Board {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) depth: usize,
+    pub(crate) wrapping: Wrapping,
+    pub(crate) neighbourhood: Neighbourhood,
+    pub(crate) cells: Vec<Tile>,
+}
+
+impl Board {
+    /// A board with every cell set to `tile`.
+    #[must_use]
+    pub fn new(
+        dimensions: Dimensions,
+        wrapping: Wrapping,
+        neighbourhood: Neighbourhood,
+        tile: &Tile,
+    ) -> Self {
+        let Dimensions {
+            width,
+            height,
+            depth,
+        } = dimensions;
+
+        Self {
+            width,
+            height,
+            depth,
+            wrapping,
+            neighbourhood,
+            cells: vec![tile.clone(); width.saturating_mul(height).saturating_mul(depth)],
+        }
+    }
+
+    /// The size the board was created with.
+    #[must_use]
+    pub const fn dimensions(&self) -> Dimensions {
+        Dimensions {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+        }
+    }
+
+    /// Which edges of the board meet.
+    #[must_use]
+    pub const fn wrapping(&self) -> Wrapping {
+        self.wrapping
+    }
+
+    /// How the board's cells are connected.
+    #[must_use]
+    pub const fn neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood
+    }
+
+    /// Every cell, in the order described by [`Board::get_cell`].
+    #[must_use]
+    pub fn cells(&self) -> &[Tile] {
+        &self.cells
+    }
+
+    /// The index of the cell at the given coordinates, or `None` if they are off the board. Cells
+    /// are stored as layers of rows, so the cell at (x, y, z) is at `(z * height + y) * width + x`.
+    #[must_use]
+    pub fn get_cell(&self, width: usize, height: usize, depth: usize) -> Option<usize> {
+        if width >= self.width || height >= self.height || depth >= self.depth {
+            return None;
+        }
+
+        depth
+            .checked_mul(self.height)?
+            .checked_add(height)?
+            .checked_mul(self.width)?
+            .checked_add(width)
+    }
+
+    /// The (width, height, depth) coordinates of a cell.
+    #[must_use]
+    pub fn get_coordinates(&self, cell: usize) -> Option<(usize, usize, usize)> {
+        let width = cell.checked_rem(self.width)?;
+        let row = cell.checked_div(self.width)?;
+
+        Some((
+            width,
+            row.checked_rem(self.height)?,
+            row.checked_div(self.height)?,
+        ))
+    }
+
+    /// The axial (q, r) coordinates of a cell of a hex board, or `None` on other boards. The r axis
+    /// runs down the rows and the q axis along them, leaning left as it goes down, so a step in a
+    /// given direction always changes them by the same amount.
+    #[must_use]
+    pub fn get_axial_coordinates(&self, cell: usize) -> Option<(isize, isize)> {
+        if self.neighbourhood != Neighbourhood::Hexagonal {
+            return None;
+        }
+
+        let (width, height, _) = self.get_coordinates(cell)?;
+        let (column, row) = (isize::try_from(width).ok()?, isize::try_from(height).ok()?);
+        // every second row is shifted right by another half cell, moving the q axis left by one
+        Some((column.checked_sub(row.checked_div(2)?)?, row))
+    }
+
+    /// The index of the cell of a hex board at the given axial coordinates and layer, or `None` if
+    /// they are off the board or the board is not a hex board.
+    #[must_use]
+    pub fn get_cell_from_axial(&self, q: isize, r: isize, depth: usize) -> Option<usize> {
+        if self.neighbourhood != Neighbourhood::Hexagonal {
+            return None;
+        }
+
+        let row = usize::try_from(r).ok()?;
+        let column = usize::try_from(q.checked_add(r.checked_div(2)?)?).ok()?;
+        self.get_cell(column, row, depth)
+    }
+
+    /// The cell at the given index.
+    #[must_use]
+    pub fn get(&self, cell: usize) -> Option<&Tile> {
+        self.cells.get(cell)
+    }
+
+    pub(crate) fn get_mut(&mut self, cell: usize) -> Option<&mut Tile> {
+        self.cells.get_mut(cell)
+    }
+
+    /// The cell one step from `cell` in `direction`, wrapping around the edges that wrap.
+    #[must_use]
+    pub fn get_neighbour(&self, cell: usize, direction: Direction) -> Option<usize> {
+        let (width, height, depth) = self.get_coordinates(cell)?;
+
+        let (del_w, del_h) = self.neighbourhood.get_deltas(direction, height % 2 == 1);
+        let new_w = Self::offset(width, del_w, self.width, self.wrapping.horizontal)?;
+        let new_h = Self::offset(height, del_h, self.height, self.wrapping.vertical)?;
+        let new_d = Self::offset(depth, direction.get_depth_delta(), self.depth, false)?;
+
+        self.get_cell(new_w, new_h, new_d)
+    }
+
+    fn offset(position: usize, delta: i8, length: usize, wraps: bool) -> Option<usize> {
+        if !wraps {
+            return position.checked_add_signed(isize::from(delta));
+        }
+
+        // stepping back by one is the same as stepping forward by `length - 1`
+        let delta =
+            usize::try_from(isize::from(delta).rem_euclid(isize::try_from(length).ok()?)).ok()?;
+        position.checked_add(delta)?.checked_rem(length)
+    }
+
+    /// Every row of every layer, front layer first.
+    pub fn rows(&self) -> impl Iterator<Item = &[Tile]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub(crate) fn get_domain(&self, tile_count: usize, cell: usize) -> Option<Bitset> {
+        match self.get(cell)? {
+            Tile::Revealed(tile) => Some(Bitset::single(tile_count, *tile)),
+            Tile::Hidden(possible_tiles) => Some(possible_tiles.choices.clone()),
+        }
+    }
+
+    pub(crate) fn get_contradiction(&self, cell: usize) -> Contradiction {
+        let (width, height, depth) = self.get_coordinates(cell).unwrap_or_default();
+        Contradiction {
+            width,
+            height,
+            depth: (self.depth > 1).then_some(depth),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_board(
+        width: usize,
+        height: usize,
+        wrapping: Wrapping,
+        neighbourhood: Neighbourhood,
+    ) -> Board {
+        let dimensions = Dimensions {
+            width,
+            height,
+            depth: 1,
+        };
+        Board::new(dimensions, wrapping, neighbourhood, &Tile::Revealed(0))
+    }
+
+    #[test]
+    fn contradictions_name_the_layer_of_layered_boards() {
+        let flat = new_board(4, 3, Wrapping::default(), Neighbourhood::VonNeumann);
+        assert_eq!(flat.get_contradiction(9).to_string(), "(1, 2)");
+
+        let dimensions = Dimensions {
+            width: 4,
+            height: 3,
+            depth: 2,
+        };
+        let layered = Board::new(
+            dimensions,
+            Wrapping::default(),
+            Neighbourhood::Voxel,
+            &Tile::Revealed(0),
+        );
+        assert_eq!(layered.get_contradiction(9).to_string(), "(1, 2, 0)");
+        assert_eq!(layered.get_contradiction(21).to_string(), "(1, 2, 1)");
+    }
+
+    #[test]
+    fn edges_stop_neighbours_without_wrapping() {
+        let board = new_board(4, 3, Wrapping::default(), Neighbourhood::Moore);
+        assert_eq!(board.get_neighbour(0, Direction::Left), None);
+        assert_eq!(board.get_neighbour(0, Direction::Up), None);
+        assert_eq!(board.get_neighbour(0, Direction::Right), Some(1));
+        assert_eq!(board.get_neighbour(0, Direction::DownRight), Some(5));
+        assert_eq!(board.get_neighbour(11, Direction::Down), None);
+        assert_eq!(board.get_neighbour(11, Direction::Right), None);
+    }
+
+    #[test]
+    fn wraps_each_axis_separately() {
+        let horizontal = Wrapping {
+            horizontal: true,
+            vertical: false,
+        };
+        let board = new_board(4, 3, horizontal, Neighbourhood::VonNeumann);
+        assert_eq!(board.get_neighbour(0, Direction::Left), Some(3));
+        assert_eq!(board.get_neighbour(7, Direction::Right), Some(4));
+        assert_eq!(board.get_neighbour(0, Direction::Up), None);
+
+        let vertical = Wrapping {
+            horizontal: false,
+            vertical: true,
+        };
+        let board = new_board(4, 3, vertical, Neighbourhood::VonNeumann);
+        assert_eq!(board.get_neighbour(1, Direction::Up), Some(9));
+        assert_eq!(board.get_neighbour(9, Direction::Down), Some(1));
+        assert_eq!(board.get_neighbour(4, Direction::Left), None);
+    }
+
+    #[test]
+    fn wraps_diagonals_across_corners() {
+        let wrapping = Wrapping {
+            horizontal: true,
+            vertical: true,
+        };
+        let board = new_board(4, 3, wrapping, Neighbourhood::Moore);
+        assert_eq!(board.get_neighbour(0, Direction::UpLeft), Some(11));
+        assert_eq!(board.get_neighbour(11, Direction::DownRight), Some(0));
+        assert_eq!(board.get_neighbour(3, Direction::UpRight), Some(8));
+    }
+
+    #[test]
+    fn hex_diagonals_depend_on_row_parity() {
+        let board = new_board(4, 4, Wrapping::default(), Neighbourhood::Hexagonal);
+        // (1, 2) is in an even row, so its diagonal neighbours are in columns 0 and 1
+        assert_eq!(board.get_neighbour(9, Direction::UpLeft), Some(4));
+        assert_eq!(board.get_neighbour(9, Direction::UpRight), Some(5));
+        assert_eq!(board.get_neighbour(9, Direction::DownLeft), Some(12));
+        assert_eq!(board.get_neighbour(9, Direction::DownRight), Some(13));
+        // (1, 1) is in an odd row, shifted right, so they are in columns 1 and 2
+        assert_eq!(board.get_neighbour(5, Direction::UpLeft), Some(1));
+        assert_eq!(board.get_neighbour(5, Direction::UpRight), Some(2));
+        assert_eq!(board.get_neighbour(5, Direction::DownLeft), Some(9));
+        assert_eq!(board.get_neighbour(5, Direction::DownRight), Some(10));
+    }
+
+    #[test]
+    fn hex_steps_have_fixed_axial_offsets() {
+        let board = new_board(5, 4, Wrapping::default(), Neighbourhood::Hexagonal);
+        let axial_offsets = [
+            (Direction::Left, (-1, 0)),
+            (Direction::Right, (1, 0)),
+            (Direction::UpLeft, (0, -1)),
+            (Direction::UpRight, (1, -1)),
+            (Direction::DownLeft, (-1, 1)),
+            (Direction::DownRight, (0, 1)),
+        ];
+
+        for cell in 0..board.cells().len() {
+            let axial = board.get_axial_coordinates(cell);
+            assert!(axial.is_some());
+            let (q, r) = axial.unwrap_or_default();
+            assert_eq!(board.get_cell_from_axial(q, r, 0), Some(cell));
+
+            for (direction, (del_q, del_r)) in axial_offsets {
+                let expected = board.get_cell_from_axial(q + del_q, r + del_r, 0);
+                assert_eq!(board.get_neighbour(cell, direction), expected);
+            }
+        }
+
+        let square = new_board(5, 4, Wrapping::default(), Neighbourhood::VonNeumann);
+        assert_eq!(square.get_axial_coordinates(0), None);
+    }
+
+    #[test]
+    fn hex_neighbours_lead_back_across_wrapped_edges() {
+        let wrapping = Wrapping {
+            horizontal: true,
+            vertical: true,
+        };
+        let board = new_board(5, 4, wrapping, Neighbourhood::Hexagonal);
+        for cell in 0..board.cells().len() {
+            for direction in Neighbourhood::Hexagonal.get_directions() {
+                let neighbour = board.get_neighbour(cell, direction);
+                assert!(neighbour.is_some());
+                assert_eq!(
+                    neighbour.and_then(|neighbour| {
+                        board.get_neighbour(neighbour, direction.get_opposite())
+                    }),
+                    Some(cell)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::iter;
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, EnumString};
+
+/// A step from a cell to one of its neighbours. Rows grow downwards and layers grow backwards.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Direction {
+    /// The previous row.
+    Up,
+    /// The next row.
+    Down,
+    /// The previous column.
+    Left,
+    /// The next column.
+    Right,
+    /// Diagonally up and left, or up and to the left on a hex grid.
+    UpLeft,
+    /// Diagonally up and right, or up and to the right on a hex grid.
+    UpRight,
+    /// Diagonally down and left, or down and to the left on a hex grid.
+    DownLeft,
+    /// Diagonally down and right, or down and to the right on a hex grid.
+    DownRight,
+    /// The previous layer.
+    Front,
+    /// The next layer.
+    Back,
+}
+
+impl Direction {
+    /// The (column, row) offset of a step on a square grid.
+    #[must_use]
+    pub const fn get_deltas(self) -> (i8, i8) {
+        match self {
+            Self::Up => (0, -1), // increasing rows go from top to bottom, so we flip signs for up and down
+            Self::Down => (0, 1),
+            Self::Left => (-1, 0),
+            Self::Right => (1, 0),
+            Self::UpLeft => (-1, -1),
+            Self::UpRight => (1, -1),
+            Self::DownLeft => (-1, 1),
+            Self::DownRight => (1, 1),
+            Self::Front | Self::Back => (0, 0),
+        }
+    }
+
+    /// Layers are numbered from the front, so going back moves to the next layer.
+    #[must_use]
+    pub const fn get_depth_delta(self) -> i8 {
+        match self {
+            Self::Front => -1,
+            Self::Back => 1,
+            _ => 0,
+        }
+    }
+
+    /// The direction leading back to the starting cell.
+    #[must_use]
+    pub const fn get_opposite(self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::UpLeft => Self::DownRight,
+            Self::UpRight => Self::DownLeft,
+            Self::DownLeft => Self::UpRight,
+            Self::DownRight => Self::UpLeft,
+            Self::Front => Self::Back,
+            Self::Back => Self::Front,
+        }
+    }
+
+    /// Turns a quarter circle clockwise in the plane of the screen, so `Front` and `Back` stay put.
+    #[must_use]
+    pub const fn rotate_clockwise(self) -> Self {
+        match self {
+            Self::Up => Self::Right,
+            Self::Right => Self::Down,
+            Self::Down => Self::Left,
+            Self::Left => Self::Up,
+            Self::UpLeft => Self::UpRight,
+            Self::UpRight => Self::DownRight,
+            Self::DownRight => Self::DownLeft,
+            Self::DownLeft => Self::UpLeft,
+            Self::Front => Self::Front,
+            Self::Back => Self::Back,
+        }
+    }
+
+    /// Turns a quarter circle clockwise as seen from above, so `Up` and `Down` stay put.
+    #[must_use]
+    pub const fn rotate_horizontally(self) -> Self {
+        match self {
+            Self::Front => Self::Left,
+            Self::Left => Self::Back,
+            Self::Back => Self::Right,
+            Self::Right => Self::Front,
+            // diagonals lie in the plane of the screen, so no horizontal turn keeps them diagonal
+            Self::UpLeft => Self::UpLeft,
+            Self::UpRight => Self::UpRight,
+            Self::DownLeft => Self::DownLeft,
+            Self::DownRight => Self::DownRight,
+            Self::Up => Self::Up,
+            Self::Down => Self::Down,
+        }
+    }
+
+    /// Turns by a sixth of a circle around the six directions of a hex grid. `Up` and `Down` are
+    /// not hex directions, so they stay put.
+    #[must_use]
+    pub const fn rotate_hex_clockwise(self) -> Self {
+        match self {
+            Self::Right => Self::DownRight,
+            Self::DownRight => Self::DownLeft,
+            Self::DownLeft => Self::Left,
+            Self::Left => Self::UpLeft,
+            Self::UpLeft => Self::UpRight,
+            Self::UpRight => Self::Right,
+            Self::Up => Self::Up,
+            Self::Down => Self::Down,
+            Self::Front => Self::Front,
+            Self::Back => Self::Back,
+        }
+    }
+
+    /// Mirrors left and right.
+    #[must_use]
+    pub const fn reflect_horizontally(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::UpLeft => Self::UpRight,
+            Self::UpRight => Self::UpLeft,
+            Self::DownLeft => Self::DownRight,
+            Self::DownRight => Self::DownLeft,
+            Self::Up | Self::Down | Self::Front | Self::Back => self,
+        }
+    }
+
+    /// Mirrors up and down.
+    #[must_use]
+    pub const fn reflect_vertically(self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::UpLeft => Self::DownLeft,
+            Self::DownLeft => Self::UpLeft,
+            Self::UpRight => Self::DownRight,
+            Self::DownRight => Self::UpRight,
+            Self::Left | Self::Right | Self::Front | Self::Back => self,
+        }
+    }
+
+    /// Mirrors front and back.
+    #[must_use]
+    pub const fn reflect_depth(self) -> Self {
+        match self {
+            Self::Front => Self::Back,
+            Self::Back => Self::Front,
+            _ => self,
+        }
+    }
+
+    /// Hex boards are stored in "odd-r" offset coordinates rather than axial ones, so that they
+    /// fit the same rectangular layout as square boards: every odd row is shifted half a cell to
+    /// the right, so the diagonal neighbours of an odd row lie one column further right than those
+    /// of an even row. [`Board::get_axial_coordinates`](crate::Board::get_axial_coordinates)
+    /// converts to axial coordinates.
+    #[must_use]
+    pub const fn get_hex_deltas(self, odd_row: bool) -> (i8, i8) {
+        match (self, odd_row) {
+            (Self::UpLeft, false) => (-1, -1),
+            (Self::UpLeft, true) | (Self::UpRight, false) | (Self::Up, _) => (0, -1),
+            (Self::UpRight, true) => (1, -1),
+            (Self::DownLeft, false) => (-1, 1),
+            (Self::DownLeft, true) | (Self::DownRight, false) | (Self::Down, _) => (0, 1),
+            (Self::DownRight, true) => (1, 1),
+            (Self::Left, _) => (-1, 0),
+            (Self::Right, _) => (1, 0),
+            (Self::Front | Self::Back, _) => (0, 0),
+        }
+    }
+
+    /// A distinct index for each direction, for looking up per-direction tables.
+    #[must_use]
+    pub const fn get_index(self) -> usize {
+        match self {
+            Self::Up => 0,
+            Self::Down => 1,
+            Self::Left => 2,
+            Self::Right => 3,
+            Self::UpLeft => 4,
+            Self::UpRight => 5,
+            Self::DownLeft => 6,
+            Self::DownRight => 7,
+            Self::Front => 8,
+            Self::Back => 9,
+        }
+    }
+}
+
+/// Which neighbours of a cell constrain it: only the four sharing an edge, also the four
+/// touching it corner to corner, the six around a cell of a hex grid, or the six sharing a face
+/// with a voxel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Neighbourhood {
+    /// The four cells sharing an edge.
+    #[default]
+    VonNeumann,
+    /// The eight cells sharing an edge or a corner.
+    Moore,
+    /// The six cells around a cell of a hex grid.
+    Hexagonal,
+    /// The six voxels sharing a face.
+    Voxel,
+}
+
+impl Neighbourhood {
+    /// The directions leading to each neighbour of a cell.
+    pub fn get_directions(self) -> impl Iterator<Item = Direction> {
+        Direction::iter().filter(move |direction| {
+            let is_diagonal = matches!(
+                direction,
+                Direction::UpLeft | Direction::UpRight | Direction::DownLeft | Direction::DownRight
+            );
+            let is_depth = matches!(direction, Direction::Front | Direction::Back);
+
+            match self {
+                Self::VonNeumann => !is_diagonal && !is_depth,
+                Self::Moore => !is_depth,
+                Self::Hexagonal => {
+                    !matches!(direction, Direction::Up | Direction::Down) && !is_depth
+                }
+                Self::Voxel => !is_diagonal,
+            }
+        })
+    }
+
+    /// The offset to the neighbour in `direction`, which on a hex grid depends on whether the
+    /// cell is in an odd row.
+    #[must_use]
+    pub const fn get_deltas(self, direction: Direction, odd_row: bool) -> (i8, i8) {
+        match self {
+            Self::VonNeumann | Self::Moore | Self::Voxel => direction.get_deltas(),
+            Self::Hexagonal => direction.get_hex_deltas(odd_row),
+        }
+    }
+
+    /// Every other direction `direction` can be turned into by rotating the grid.
+    #[must_use]
+    pub fn get_rotations(self, direction: Direction) -> Vec<Direction> {
+        let (turns, rotate): (usize, fn(Direction) -> Direction) = match self {
+            // diagonals only rotate onto diagonals, so corner contacts are never mistaken for edges
+            Self::VonNeumann | Self::Moore => (3, Direction::rotate_clockwise),
+            Self::Hexagonal => (5, Direction::rotate_hex_clockwise),
+            // structures keep their up and down, so voxels only turn around the vertical axis
+            Self::Voxel => (3, Direction::rotate_horizontally),
+        };
+
+        iter::successors(Some(rotate(direction)), |rotated| Some(rotate(*rotated)))
+            .take(turns)
+            .collect()
+    }
+
+    /// Every other direction `direction` can be turned into by mirroring the grid. Voxels are
+    /// never flipped upside down.
+    #[must_use]
+    pub const fn get_reflections(self, direction: Direction) -> [Direction; 2] {
+        match self {
+            Self::VonNeumann | Self::Moore | Self::Hexagonal => [
+                direction.reflect_horizontally(),
+                direction.reflect_vertically(),
+            ],
+            Self::Voxel => [direction.reflect_horizontally(), direction.reflect_depth()],
+        }
+    }
+
+    /// The directions a rule learned in `direction` also holds in.
+    #[must_use]
+    pub fn get_symmetric_directions(
+        self,
+        direction: Direction,
+        augmentation: Augmentation,
+    ) -> Vec<Direction> {
+        if augmentation.isotropic {
+            return self.get_directions().collect();
+        }
+
+        let mut directions = vec![direction];
+        if augmentation.reflections {
+            directions.extend(self.get_reflections(direction));
+        }
+        if augmentation.rotations {
+            // rotating the mirrored directions as well covers every symmetry of the grid
+            for mirrored in directions.clone() {
+                directions.extend(self.get_rotations(mirrored));
+            }
+        }
+
+        directions
+    }
+}
+
+/// How the rules learned from a sample are extended to other orientations.
+///
+/// Rotations and reflections keep the sample's structure, turned or mirrored, while an isotropic
+/// generation allows every learned adjacency in every direction.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Augmentation {
+    /// Learn every rotation of the sample.
+    pub rotations: bool,
+    /// Learn the mirror images of the sample.
+    pub reflections: bool,
+    /// Allow every learned adjacency in every direction.
+    pub isotropic: bool,
+}
+
+impl Augmentation {
+    /// Allows every learned adjacency in every direction.
+    pub const ISOTROPIC: Self = Self {
+        rotations: false,
+        reflections: false,
+        isotropic: true,
+    };
+}
@@ -0,0 +1,123 @@
+use crate::board::Contradiction;
+use core::fmt;
+use imgproc_rs::error::ImgIoError;
+
+/// Everything that can go wrong while learning rules from a sample or solving a board.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io {
+        /// The file that failed.
+        path: String,
+        /// What the operating system or image decoder reported.
+        message: String,
+    },
+    /// A pixel of the sample has fewer than the three channels a colour needs.
+    UnsupportedChannelCount {
+        /// How many channels the pixel had.
+        channels: usize,
+    },
+    /// A position or size did not fit in the integer type it is stored in.
+    CoordinateOverflow,
+    /// The sample produced no adjacency rules, so there is nothing to build a board from.
+    EmptyRuleset,
+    /// A line of a tileset could not be understood.
+    InvalidTileset {
+        /// The line, counting from one.
+        line: usize,
+        /// What was wrong with it.
+        reason: &'static str,
+    },
+    /// A `MagicaVoxel` file is malformed.
+    InvalidVox {
+        /// What was wrong with it.
+        reason: &'static str,
+    },
+    /// The model cannot be used with the requested settings.
+    Unsupported {
+        /// Which combination is not supported.
+        reason: &'static str,
+    },
+    /// A tile was named that the generation does not contain.
+    UnknownTile,
+    /// A tile weight is negative or not finite, or would make the weights of the generation sum
+    /// past what a float can hold.
+    InvalidWeight {
+        /// The weight that was rejected.
+        weight: f64,
+    },
+    /// The pinned and banned cells of a board cannot all be satisfied.
+    ConflictingPins {
+        /// The cell that was left without a choice.
+        contradiction: Contradiction,
+    },
+    /// Every attempt at solving the board ended in a contradiction.
+    Contradiction {
+        /// How many boards were tried.
+        attempts: u32,
+        /// Where each attempt failed, in order.
+        contradictions: Vec<Contradiction>,
+    },
+}
+
+impl Error {
+    pub(crate) fn from_image(path: &str, error: ImgIoError) -> Self {
+        let message = match error {
+            ImgIoError::IoError(error) => error.to_string(),
+            ImgIoError::ImageReaderError(error) => error.to_string(),
+            ImgIoError::UnsupportedFileFormatError(message)
+            | ImgIoError::UnsupportedColorTypeError(message)
+            | ImgIoError::ImageWriteError(message)
+            | ImgIoError::OtherError(message) => message,
+        };
+
+        Self::Io {
+            path: path.to_owned(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, message } => write!(formatter, "Could not access {path}: {message}."),
+            Self::UnsupportedChannelCount { channels } => write!(
+                formatter,
+                "Pixels need at least three channels, but the sample has {channels}."
+            ),
+            Self::CoordinateOverflow => write!(formatter, "A coordinate is too large."),
+            Self::EmptyRuleset => write!(formatter, "The sample does not produce any rules."),
+            Self::InvalidTileset { line, reason } => {
+                write!(formatter, "Line {line} of the tileset: {reason}.")
+            }
+            Self::InvalidVox { reason } => write!(formatter, "Invalid MagicaVoxel file: {reason}."),
+            Self::Unsupported { reason } => write!(formatter, "{reason}."),
+            Self::UnknownTile => write!(formatter, "The rules contain no such tile."),
+            Self::InvalidWeight { .. } => write!(
+                formatter,
+                "Weights must be finite, non-negative and small enough to add up."
+            ),
+            Self::ConflictingPins { contradiction } => write!(
+                formatter,
+                "The pinned and banned cells leave no choice for {contradiction}."
+            ),
+            Self::Contradiction {
+                attempts,
+                contradictions,
+            } => {
+                let locations = contradictions
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(
+                    formatter,
+                    "All {attempts} attempts ended in a contradiction, at {locations}."
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
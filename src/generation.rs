@@ -0,0 +1,206 @@
+use crate::bitset::Bitset;
+use crate::board::weight_log_weight;
+use crate::direction::{Direction, Neighbourhood};
+use crate::error::Error;
+use crate::rule::Rule;
+use crate::tile::TileType;
+use core::hash::Hash;
+use std::collections::{HashMap, HashSet};
+use strum::IntoEnumIterator;
+
+/// The tiles a board can be made of, which of them may sit next to each other and how likely
+/// each one is to be chosen.
+#[derive(Debug)]
+pub struct Generation<T = TileType> {
+    // every tile that takes part in a rule, so the solver can refer to tiles by index
+    pub(crate) tiles: Vec<T>,
+    pub(crate) indices: HashMap<T, usize>,
+    // how likely each tile in `tiles` is to be chosen, learned from how often it was seen unless
+    // overridden with `set_weight`
+    pub(crate) weights: Vec<f64>,
+    // for every tile index, the tiles allowed next to it in each direction, indexed by
+    // `Direction::get_index`
+    pub(crate) compatible: Vec<Vec<Bitset>>,
+    // the directions the rules were learned in, and so the ones enforced while solving
+    pub(crate) neighbourhood: Neighbourhood,
+}
+
+impl<T: Copy + Ord + Hash> Generation<T> {
+    /// Builds a generation from a set of rules and how often each tile appeared. Tiles missing
+    /// from `frequencies` get a weight of zero.
+    #[must_use]
+    pub fn new<W: Copy + Into<f64>>(
+        ruleset: &HashSet<Rule<T>>,
+        frequencies: &HashMap<T, W>,
+        neighbourhood: Neighbourhood,
+    ) -> Self {
+        // sorted so that tile indices, and with them every choice the solver makes, are stable
+        let mut tiles = get_all_tile_types(ruleset).into_iter().collect::<Vec<T>>();
+        tiles.sort_unstable();
+        let indices = tiles
+            .iter()
+            .enumerate()
+            .map(|(index, tile)| (*tile, index))
+            .collect::<HashMap<T, usize>>();
+        let weights = tiles
+            .iter()
+            .map(|tile| frequencies.get(tile).map_or(0.0, |weight| (*weight).into()))
+            .collect();
+
+        let tile_count = tiles.len();
+        let mut compatible =
+            vec![vec![Bitset::new(tile_count); Direction::iter().count()]; tile_count];
+        for rule in ruleset {
+            let (Some(from), Some(to)) = (indices.get(&rule.from), indices.get(&rule.to)) else {
+                continue;
+            };
+            if let Some(mask) = compatible
+                .get_mut(*from)
+                .and_then(|masks| masks.get_mut(rule.direction.get_index()))
+            {
+                mask.insert(*to);
+            }
+        }
+
+        Self {
+            tiles,
+            indices,
+            weights,
+            compatible,
+            neighbourhood,
+        }
+    }
+
+    /// Every tile, in index order.
+    #[must_use]
+    pub fn tiles(&self) -> &[T] {
+        &self.tiles
+    }
+
+    /// The weight of every tile, in index order.
+    #[must_use]
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// The directions the rules hold in.
+    #[must_use]
+    pub const fn neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood
+    }
+
+    /// The tile a revealed cell refers to.
+    #[must_use]
+    pub fn get_tile(&self, index: usize) -> Option<T> {
+        self.tiles.get(index).copied()
+    }
+
+    /// The index cells use to refer to `tile`.
+    #[must_use]
+    pub fn get_index(&self, tile: T) -> Option<usize> {
+        self.indices.get(&tile).copied()
+    }
+
+    /// Overrides how likely `tile` is to be chosen.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownTile`] if the generation has no such tile, and
+    /// [`Error::InvalidWeight`] if the weight is negative or not finite, or the weights would sum
+    /// past what a float can hold.
+    pub fn set_weight(&mut self, tile: T, weight: f64) -> Result<(), Error> {
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(Error::InvalidWeight { weight });
+        }
+        let index = self.get_index(tile).ok_or(Error::UnknownTile)?;
+
+        // the entropy sums of a cell add up its weights and their `w * ln(w)` terms
+        let weights = || {
+            self.weights
+                .iter()
+                .enumerate()
+                .map(|(other, current)| if other == index { weight } else { *current })
+        };
+        let weight_sum = weights().sum::<f64>();
+        let weight_log_weight_sum = weights()
+            .map(|weight| weight_log_weight(weight).abs())
+            .sum::<f64>();
+        if !weight_sum.is_finite() || !weight_log_weight_sum.is_finite() {
+            return Err(Error::InvalidWeight { weight });
+        }
+
+        let current = self.weights.get_mut(index).ok_or(Error::UnknownTile)?;
+        *current = weight;
+        Ok(())
+    }
+
+    pub(crate) fn get_mask(&self, index: usize, direction: Direction) -> Option<&Bitset> {
+        self.compatible.get(index)?.get(direction.get_index())
+    }
+
+    /// The tiles allowed next to `tile` in `direction`, in index order.
+    #[must_use]
+    pub fn get_compatible(&self, tile: T, direction: Direction) -> Vec<T> {
+        self.get_index(tile)
+            .and_then(|index| self.get_mask(index, direction))
+            .map(|mask| mask.iter().filter_map(|to| self.get_tile(to)).collect())
+            .unwrap_or_default()
+    }
+}
+
+pub fn get_all_tile_types<T: Copy + Ord + Hash>(ruleset: &HashSet<Rule<T>>) -> HashSet<T> {
+    let mut result = HashSet::<T>::new();
+
+    for rule in ruleset {
+        result.insert(rule.from);
+        result.insert(rule.to);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_tiles() -> Generation<u8> {
+        let ruleset = HashSet::from([Rule::new(0_u8, 1, Direction::Right)]);
+        Generation::new(
+            &ruleset,
+            &HashMap::from([(0, 1.0), (1, 1.0)]),
+            Neighbourhood::VonNeumann,
+        )
+    }
+
+    #[test]
+    fn overrides_weights() {
+        let mut generation = two_tiles();
+        assert!(generation.set_weight(1, 2.5).is_ok());
+        assert_eq!(generation.weights(), [1.0, 2.5]);
+        assert!(generation.set_weight(1, 0.0).is_ok());
+        assert_eq!(generation.weights(), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn rejects_unknown_tiles_and_invalid_weights() {
+        let mut generation = two_tiles();
+        assert!(matches!(
+            generation.set_weight(7, 1.0),
+            Err(Error::UnknownTile)
+        ));
+        for weight in [-1.0, f64::NAN, f64::INFINITY, f64::MAX] {
+            assert!(matches!(
+                generation.set_weight(0, weight),
+                Err(Error::InvalidWeight { .. })
+            ));
+        }
+
+        // a rejected override leaves the earlier ones in place
+        assert!(generation.set_weight(0, 1e300).is_ok());
+        assert!(matches!(
+            generation.set_weight(1, f64::MAX / 2.0),
+            Err(Error::InvalidWeight { .. })
+        ));
+        assert_eq!(generation.weights(), [1e300, 1.0]);
+    }
+}
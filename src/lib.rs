@@ -0,0 +1,72 @@
+//! Wave function collapse algorithm written in Rust.
+//!
+//! Rules are learned from a sample with one of the model constructors: [`generation_init`] treats
+//! every pixel of an image as a tile, [`overlapping_init`] uses the square patterns of an image,
+//! [`tileset_init`] reads hand-written adjacencies and [`voxel_init`] learns from a `MagicaVoxel`
+//! model. The resulting [`Generation`] is handed to a [`Solver`], which fills a [`Board`] with
+//! tiles that obey every rule. Cells can be fixed beforehand with [`Solver::pin`] and
+//! [`Solver::ban`], so that only the rest of the board is generated around them.
+//!
+//! A solver can also be driven one [`Solver::step`] at a time, or for a time budget with
+//! [`Solver::run_for`], and reports each collapse and contradiction to an [`Observer`]. The
+//! boards it reports can be drawn with [`get_colours`] and saved as an animation with
+//! [`GifWriter`].
+//!
+//! ```no_run
+//! use wavefunction_collapse::{generation_init, Augmentation, Dimensions, Neighbourhood, Solver};
+//!
+//! # fn main() -> Result<(), wavefunction_collapse::Error> {
+//! let generation = generation_init(
+//!     "./resources/beach.bmp",
+//!     Augmentation::ISOTROPIC,
+//!     false,
+//!     Neighbourhood::VonNeumann,
+//! )?;
+//! let dimensions = Dimensions {
+//!     width: 40,
+//!     height: 30,
+//!     depth: 1,
+//! };
+//! let board = Solver::new(&generation, dimensions).with_seed(7).run()?;
+//! for row in board.rows() {
+//!     println!("{row:?}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#![warn(missing_docs)]
+#![allow(clippy::missing_docs_in_private_items)]
+#![allow(clippy::pattern_type_mismatch)]
+#![allow(clippy::single_call_fn)]
+
+mod bitset;
+mod board;
+mod direction;
+mod error;
+mod generation;
+mod overlapping;
+mod render;
+mod rule;
+mod sample;
+mod solver;
+mod tile;
+mod tiled;
+mod voxel;
+
+pub use bitset::Bitset;
+pub use board::{Board, Contradiction, Dimensions, PossibleTiles, Tile, Wrapping};
+pub use direction::{Augmentation, Direction, Neighbourhood};
+pub use error::Error;
+pub use generation::Generation;
+pub use overlapping::{overlapping_init, OverlappingModel, Pattern, PatternId};
+pub use render::{
+    get_colours, render_ansi, render_graphics, write_image, ColourDepth, GifWriter,
+    GraphicsProtocol, HEX_MARGIN,
+};
+pub use rule::Rule;
+pub use sample::{generation_init, read_image};
+pub use solver::{Backtracking, Observer, Progress, Solver};
+pub use tile::{TileType, COAST_TILE, GRASS_TILE, INVALID_TILE, WATER_TILE};
+pub use tiled::{tileset_init, Symmetry, TileDefinition, TiledModel, TiledTile};
+pub use voxel::{read_vox, voxel_init, write_vox, Voxel, VoxelGrid, VoxelModel, EMPTY_VOXEL};
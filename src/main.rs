@@ -1,391 +1,713 @@
-//! Wave function collapse algorithm written in Rust.
+//! Command line interface that generates boards from the samples in `resources`.
 
 #![allow(clippy::missing_docs_in_private_items)]
 #![allow(clippy::pattern_type_mismatch)]
 #![allow(clippy::single_call_fn)]
 
-use core::hash::BuildHasher;
-use imgproc_rs::image::BaseImage;
-use imgproc_rs::io;
-use rand::distributions::{Distribution, Uniform};
-use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use core::fmt::Debug;
+use core::hash::Hash;
 use std::env;
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
-
-macro_rules! unwrap_result_or_return {
-    ( $e:expr ) => {
-        match $e {
-            Ok(x) => x,
-            Err(_) => return,
-        }
-    };
-}
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::ExitCode;
+use std::str::FromStr;
+use wavefunction_collapse::{
+    generation_init, get_colours, overlapping_init, read_image, render_ansi, render_graphics,
+    tileset_init, voxel_init, write_image, write_vox, Augmentation, Backtracking, Board,
+    ColourDepth, Contradiction, Dimensions, Error, Generation, GifWriter, GraphicsProtocol,
+    Neighbourhood, Observer, Solver, TileType, Wrapping, INVALID_TILE,
+};
 
-macro_rules! unwrap_option_or_return {
-    ( $e:expr ) => {
-        match $e {
-            Some(x) => x,
-            None => return,
+const USAGE: &str = "\
+Usage: wavefunction_collapse <sample> [options]
+
+The sample is read from the resources directory: a .bmp image, a .tileset definition or a .vox
+model.
+
+Board:
+  --width <cells>            Width of the board (default 20)
+  --height <cells>           Height of the board (default 20)
+  --depth <layers>           Layers of a voxel board (default 20, .vox only)
+  --periodic                 Wrap the board around both axes
+  --periodic-x, --periodic-y Wrap the board around one axis
+  --moore                    Make diagonal cells neighbours as well
+  --hex                      Use a hex grid with odd rows shifted right
+
+Learning:
+  --periodic-input           Treat the sample as tileable
+  --overlapping <size>       Learn size by size patterns instead of single pixels
+  --rotations                Also learn the rotations of the sample
+  --reflections              Also learn the mirror images of the sample
+  --isotropic                Allow every learned adjacency in every direction
+  --weight <r>,<g>,<b>=<w>   Override how likely a colour is (simple model only, repeatable)
+  --print-compatibility      List which tiles may sit next to each other
+
+Solving:
+  --seed <number>            Seed for reproducible output (default random)
+  --attempts <count>         Fresh boards to try after a contradiction (default 1)
+  --max-backtracks <count>   Undo decisions to get out of contradictions (default 0)
+  --backtrack-depth <count>  How many past decisions can be undone (default unlimited)
+  --inpaint <image>          Fill in the pixels of an image that have the --unknown colour
+  --unknown <r>,<g>,<b>      The colour of the pixels to fill in (default 255,0,0)
+
+Output:
+  --output <path>            Save the board as an image, or as a .vox model for voxel samples
+  --scale <pixels>           Size of each cell in saved and inline images
+  --graphics <kitty|sixel>   Show the board as an inline image with this protocol
+  --no-graphics              Always show the board as coloured text
+  --256-colours              Only use the 256-colour terminal palette
+  --record <path>            Save every collapse as a .gif, or as numbered images
+  --record-propagation       Also record each cell narrowed down by propagation
+  --frame-delay <hundredths> Time between the frames of a .gif (default 4)
+  --help                     Show this message
+";
+
+// Flags that are followed by a value.
+const VALUE_FLAGS: [&str; 16] = [
+    "--attempts",
+    "--backtrack-depth",
+    "--depth",
+    "--frame-delay",
+    "--graphics",
+    "--height",
+    "--inpaint",
+    "--max-backtracks",
+    "--output",
+    "--overlapping",
+    "--record",
+    "--scale",
+    "--seed",
+    "--unknown",
+    "--weight",
+    "--width",
+];
+
+// Flags that take effect by being present.
+const SWITCH_FLAGS: [&str; 13] = [
+    "--256-colours",
+    "--hex",
+    "--isotropic",
+    "--moore",
+    "--no-graphics",
+    "--periodic",
+    "--periodic-input",
+    "--periodic-x",
+    "--periodic-y",
+    "--print-compatibility",
+    "--record-propagation",
+    "--reflections",
+    "--rotations",
+];
+
+// Rejects anything after the sample that is not a known flag, so that typos are not ignored.
+fn check_flags(args: &[String]) -> Result<(), String> {
+    let mut remaining = args.iter().skip(2);
+    while let Some(arg) = remaining.next() {
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            remaining.next();
+        } else if !SWITCH_FLAGS.contains(&arg.as_str()) {
+            return Err(format!(
+                "Unknown argument \"{arg}\", run with --help to see the options."
+            ));
         }
-    };
-}
+    }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, EnumIter)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
+    Ok(())
 }
 
-impl Direction {
-    pub const fn get_deltas(self) -> (i8, i8) {
-        match self {
-            Self::Up => (0, -1), // increasing rows go from top to bottom, so we flip signs for up and down
-            Self::Down => (0, 1),
-            Self::Left => (-1, 0),
-            Self::Right => (1, 0),
-        }
-    }
+fn parse_flag<T: FromStr>(args: &[String], flag: &str) -> Result<Option<T>, String> {
+    let Some(position) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    let Some(value) = position.checked_add(1).and_then(|index| args.get(index)) else {
+        return Err(format!("Missing value for {flag}."));
+    };
 
-    pub const fn get_opposite(self) -> Self {
-        match self {
-            Self::Up => Self::Down,
-            Self::Down => Self::Up,
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-        }
-    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("Invalid value \"{value}\" for {flag}."))
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
-struct TileType {
-    pub rgb: [u8; 3],
+fn get_flag_values<'args>(args: &'args [String], flag: &str) -> Vec<&'args str> {
+    args.windows(2)
+        .filter(|pair| pair.first().is_some_and(|arg| arg == flag))
+        .filter_map(|pair| pair.get(1).map(String::as_str))
+        .collect()
 }
 
-const INVALID_TILE: TileType = TileType { rgb: [255, 0, 0] };
-const WATER_TILE: TileType = TileType { rgb: [63, 72, 204] };
-const COAST_TILE: TileType = TileType {
-    rgb: [255, 201, 14],
-};
-const GRASS_TILE: TileType = TileType { rgb: [34, 177, 76] };
-
-impl TileType {
-    pub fn from_pixel(image: &dyn BaseImage<u8>, width: u32, height: u32) -> Option<Self> {
-        let raw_pixel = image.get_pixel(width, height);
-        let (Some(first), Some(second), Some(third)) =
-            (raw_pixel.first(), raw_pixel.get(1), raw_pixel.get(2))
-        else {
-            println!("Pixel does not have three values.");
-            return None;
+// Applies every `--weight <r>,<g>,<b>=<weight>` argument to the generation.
+fn apply_weight_overrides(generation: &mut Generation, args: &[String]) -> Result<(), String> {
+    for weight_override in get_flag_values(args, "--weight") {
+        let Some((tile, weight)) = weight_override.split_once('=').and_then(|(tile, weight)| {
+            Some((TileType::from_str(tile).ok()?, weight.parse::<f64>().ok()?))
+        }) else {
+            return Err(format!("Invalid value \"{weight_override}\" for --weight."));
         };
 
-        Some(Self {
-            rgb: [*first, *second, *third],
-        })
+        generation.set_weight(tile, weight).map_err(|error| {
+            format!("Invalid value \"{weight_override}\" for --weight. {error}")
+        })?;
     }
+
+    Ok(())
 }
 
-impl<'src, S> FromIterator<&'src TileType> for HashSet<TileType, S>
-where
-    S: BuildHasher + Default,
-{
-    fn from_iter<T: IntoIterator<Item = &'src TileType>>(iter: T) -> Self {
-        iter.into_iter().copied().collect()
-    }
+fn has_extension(file_name: &str, extension: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .is_some_and(|found| found.eq_ignore_ascii_case(extension))
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
-struct Rule {
-    pub from: TileType,
-    pub to: TileType,
-    pub direction: Direction,
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
 }
 
-impl Rule {
-    pub const fn new(from: TileType, to: TileType, direction: Direction) -> Self {
-        Self {
-            from,
-            to,
-            direction,
+fn print_compatibility<T: Copy + Debug + Ord + Hash>(generation: &Generation<T>) {
+    for tile in generation.tiles() {
+        println!("{tile:?}");
+        for direction in generation.neighbourhood().get_directions() {
+            println!(
+                "    {direction:?}: {:?}",
+                generation.get_compatible(*tile, direction)
+            );
         }
     }
+}
+
+#[derive(Clone, Debug)]
+struct Settings {
+    pub backtracking: Backtracking,
+    pub attempts: u32,
+    pub overlapping: Option<u32>,
+    pub seed: u64,
+    pub dimensions: Dimensions,
+    pub wrapping: Wrapping,
+    pub periodic_input: bool,
+    pub neighbourhood: Neighbourhood,
+    pub output: Option<String>,
+    pub colour_depth: ColourDepth,
+    // shows the board as an inline image instead of coloured text, when the terminal supports it
+    pub graphics: Option<GraphicsProtocol>,
+    // how many pixels wide each cell of a saved image is
+    pub scale: Option<u32>,
+    // where the frames of the collapse are saved, as a GIF or as numbered images
+    pub record: Option<String>,
+    // also saves a frame whenever propagation narrows a cell, not just when one is collapsed
+    pub record_propagation: bool,
+    // hundredths of a second between the frames of a GIF
+    pub frame_delay: u16,
+    // the painted pixels of an image to fill in, with its unknown pixels left as `None`
+    pub inpaint: Option<Vec<Vec<Option<TileType>>>>,
+    // left unset when no symmetry flag is passed, so each model can pick its own default
+    pub augmentation: Option<Augmentation>,
+}
 
-    pub const fn reverse(from: TileType, to: TileType, direction: Direction) -> Self {
-        Self {
-            from: to,
-            to: from,
-            direction: direction.get_opposite(),
+fn parse_inpaint(
+    args: &[String],
+    is_voxel: bool,
+) -> Result<Option<Vec<Vec<Option<TileType>>>>, String> {
+    let Some(path) = parse_flag::<String>(args, "--inpaint")? else {
+        if has_flag(args, "--unknown") {
+            return Err("--unknown can only be used with --inpaint.".to_owned());
         }
+        return Ok(None);
+    };
+    if is_voxel {
+        return Err("--inpaint cannot be used with .vox samples.".to_owned());
+    }
+    if has_flag(args, "--width") || has_flag(args, "--height") {
+        return Err("--inpaint takes the size of the board from the image.".to_owned());
     }
-}
 
-#[derive(Debug)]
-struct Generation {
-    pub ruleset: HashSet<Rule>,
-    pub frequencies: HashMap<TileType, u32>,
+    let unknown = parse_flag(args, "--unknown")?.unwrap_or(INVALID_TILE);
+    let pixels = read_image(&path)
+        .map_err(|error| format!("Failed to read the image to fill in. {error}"))?;
+    Ok(Some(
+        pixels
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|colour| (colour != unknown).then_some(colour))
+                    .collect()
+            })
+            .collect(),
+    ))
 }
 
-fn get_all_tile_types(ruleset: &HashSet<Rule>) -> HashSet<TileType> {
-    let mut result = HashSet::<TileType>::new();
-
-    for rule in ruleset {
-        result.insert(rule.from);
-        result.insert(rule.to);
+fn parse_settings(args: &[String]) -> Result<Settings, String> {
+    check_flags(args)?;
+    // only voxel samples produce boards with more than one layer
+    let is_voxel = args
+        .get(1)
+        .is_some_and(|file_name| has_extension(file_name, "vox"));
+    let depth = parse_flag(args, "--depth")?;
+    if depth.is_some() && !is_voxel {
+        return Err("--depth can only be used with .vox samples.".to_owned());
+    }
+    let attempts = parse_flag(args, "--attempts")?.unwrap_or(1);
+    if attempts == 0 {
+        return Err("--attempts must be at least 1.".to_owned());
+    }
+    let overlapping = parse_flag(args, "--overlapping")?;
+    if overlapping.is_some() && is_voxel {
+        return Err("--overlapping cannot be used with .vox samples.".to_owned());
     }
+    let output = parse_flag(args, "--output")?;
+    let scale = parse_flag(args, "--scale")?;
+    if scale.is_some() && is_voxel {
+        return Err("--scale cannot be used with .vox samples.".to_owned());
+    }
+    if scale == Some(0) {
+        return Err("--scale must be at least 1.".to_owned());
+    }
+    let record = parse_flag::<String>(args, "--record")?;
+    if record.is_some() && is_voxel {
+        return Err("--record cannot be used with .vox samples.".to_owned());
+    }
+    if record
+        .as_ref()
+        .is_some_and(|path| Path::new(path).extension().is_none())
+    {
+        return Err("--record needs a file extension, such as .gif or .png.".to_owned());
+    }
+    let inpaint = parse_inpaint(args, is_voxel)?;
+    // a board being filled in is the size of the painted image
+    let (width, height) = match &inpaint {
+        Some(pixels) => (pixels.first().map_or(0, Vec::len), pixels.len()),
+        None => (
+            parse_flag(args, "--width")?.unwrap_or(20),
+            parse_flag(args, "--height")?.unwrap_or(20),
+        ),
+    };
 
-    result
+    Ok(Settings {
+        backtracking: Backtracking {
+            budget: parse_flag(args, "--max-backtracks")?.unwrap_or(0),
+            depth: parse_flag(args, "--backtrack-depth")?.unwrap_or(usize::MAX),
+        },
+        attempts,
+        overlapping,
+        seed: parse_flag(args, "--seed")?.unwrap_or_else(rand::random),
+        dimensions: Dimensions {
+            width,
+            height,
+            depth: depth.unwrap_or(if is_voxel { 20 } else { 1 }),
+        },
+        wrapping: Wrapping {
+            horizontal: has_flag(args, "--periodic") || has_flag(args, "--periodic-x"),
+            vertical: has_flag(args, "--periodic") || has_flag(args, "--periodic-y"),
+        },
+        periodic_input: has_flag(args, "--periodic-input"),
+        neighbourhood: match (has_flag(args, "--moore"), has_flag(args, "--hex")) {
+            (true, true) => return Err("Cannot use both --moore and --hex.".to_owned()),
+            (true, false) => Neighbourhood::Moore,
+            (false, true) => Neighbourhood::Hexagonal,
+            (false, false) => Neighbourhood::VonNeumann,
+        },
+        output,
+        colour_depth: if has_flag(args, "--256-colours") {
+            ColourDepth::Palette256
+        } else {
+            ColourDepth::from_env()
+        },
+        graphics: match parse_flag(args, "--graphics")? {
+            Some(protocol) => Some(protocol),
+            None if has_flag(args, "--no-graphics") || !io::stdout().is_terminal() => None,
+            None => GraphicsProtocol::from_env(),
+        },
+        scale,
+        record,
+        record_propagation: has_flag(args, "--record-propagation"),
+        frame_delay: parse_flag(args, "--frame-delay")?.unwrap_or(4),
+        inpaint,
+        augmentation: parse_augmentation(args)?,
+    })
 }
 
-fn add_adjacent_rules(
-    ruleset: &mut HashSet<Rule>,
-    image: &dyn BaseImage<u8>,
-    width: u32,
-    height: u32,
-    rotate_rules: bool,
-) {
-    let (max_width, max_height) = image.info().wh();
-
-    let from = unwrap_option_or_return!(TileType::from_pixel(image, width, height));
-
-    for direction in Direction::iter() {
-        let (del_w, del_h) = direction.get_deltas();
-
-        let raw_new_w = del_w
-            .checked_add(unwrap_result_or_return!(i8::try_from(width)))
-            .unwrap_or(i8::MAX);
-        let raw_new_h = del_h
-            .checked_add(unwrap_result_or_return!(i8::try_from(height)))
-            .unwrap_or(i8::MAX);
-
-        if raw_new_w < 0 || raw_new_h < 0 {
-            continue;
-        }
+fn get_solver<'generation, T: Copy + Ord + Hash>(
+    generation: &'generation Generation<T>,
+    settings: &Settings,
+) -> Solver<'generation, T> {
+    Solver::new(generation, settings.dimensions)
+        .with_wrapping(settings.wrapping)
+        .with_backtracking(settings.backtracking)
+        .with_attempts(settings.attempts)
+        .with_seed(settings.seed)
+}
 
-        let new_w = unwrap_result_or_return!(u32::try_from(raw_new_w));
-        let new_h = unwrap_result_or_return!(u32::try_from(raw_new_h));
+// Where the frames of a recording go.
+enum FrameSink {
+    Gif(GifWriter),
+    // `frames/step.png` becomes `frames/step_00000.png`, `frames/step_00001.png` and so on
+    Images { path: String },
+}
 
-        if (0..max_width).contains(&new_w) && (0..max_height).contains(&new_h) {
-            let to = unwrap_option_or_return!(TileType::from_pixel(image, new_w, new_h));
+// Saves a frame of the board whenever a solver reports a change to it.
+struct Recording<'generation, T, F> {
+    pub sink: FrameSink,
+    pub scale: u32,
+    pub frames: usize,
+    pub generation: &'generation Generation<T>,
+    pub get_colour: F,
+    pub record_propagation: bool,
+    // the first failure stops the recording, but the board is still solved
+    pub result: Result<(), Error>,
+}
 
-            ruleset.insert(Rule::new(from, to, direction));
-            ruleset.insert(Rule::reverse(from, to, direction));
+impl<'generation, T: Copy + Ord + Hash, F: Fn(T) -> TileType> Recording<'generation, T, F> {
+    pub fn create(
+        path: &str,
+        settings: &Settings,
+        generation: &'generation Generation<T>,
+        get_colour: F,
+    ) -> Result<Self, Error> {
+        let scale = settings.scale.unwrap_or(1);
+        let sink = if has_extension(path, "gif") {
+            FrameSink::Gif(GifWriter::create(
+                path,
+                settings.dimensions,
+                settings.neighbourhood,
+                scale,
+                settings.frame_delay,
+            )?)
+        } else {
+            FrameSink::Images {
+                path: path.to_owned(),
+            }
+        };
 
-            if rotate_rules {
-                for rotate_direction in Direction::iter() {
-                    ruleset.insert(Rule::new(from, to, rotate_direction));
-                    ruleset.insert(Rule::reverse(from, to, rotate_direction));
-                }
+        Ok(Self {
+            sink,
+            scale,
+            frames: 0,
+            generation,
+            get_colour,
+            record_propagation: settings.record_propagation,
+            result: Ok(()),
+        })
+    }
+
+    pub fn write_frame(&mut self, pixels: &[Vec<Option<TileType>>]) -> Result<(), Error> {
+        match &mut self.sink {
+            FrameSink::Gif(writer) => writer.write_frame(pixels)?,
+            FrameSink::Images { path } => {
+                let path = Path::new(path);
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let extension = path.extension().unwrap_or_default().to_string_lossy();
+                let frame_path =
+                    path.with_file_name(format!("{stem}_{:05}.{extension}", self.frames));
+                write_image(
+                    pixels,
+                    self.generation.neighbourhood(),
+                    &frame_path.to_string_lossy(),
+                    self.scale,
+                )?;
             }
         }
+        self.frames = self.frames.saturating_add(1);
+
+        Ok(())
     }
-}
 
-fn update_frequencies(
-    frequencies: &mut HashMap<TileType, u32>,
-    image: &dyn BaseImage<u8>,
-    width: u32,
-    height: u32,
-) {
-    let tile = unwrap_option_or_return!(TileType::from_pixel(image, width, height));
+    pub fn write_board(&mut self, board: &Board) {
+        if self.result.is_ok() {
+            let pixels = get_colours(board, self.generation, &self.get_colour);
+            self.result = self.write_frame(&pixels);
+        }
+    }
 
-    if let Entry::Vacant(entry) = frequencies.entry(tile) {
-        entry.insert(1);
-    } else {
-        *unwrap_option_or_return!(frequencies.get_mut(&tile)) =
-            unwrap_option_or_return!(frequencies.get_mut(&tile))
-                .checked_add(1)
-                .unwrap_or(u32::MAX);
+    pub fn finish(self) -> Result<usize, Error> {
+        self.result?;
+        if let FrameSink::Gif(writer) = self.sink {
+            writer.finish()?;
+        }
+
+        Ok(self.frames)
     }
 }
 
-fn generation_init(input_path: &str, rotate_rules: bool) -> Option<Generation> {
-    let mut ruleset = HashSet::<Rule>::new();
-    let mut frequencies = HashMap::<TileType, u32>::new();
-
-    let Ok(image) = io::read(input_path) else {
-        return None;
-    };
+impl<T: Copy + Ord + Hash, F: Fn(T) -> TileType> Observer for Recording<'_, T, F> {
+    fn on_collapse(&mut self, board: &Board, _cell: usize) {
+        self.write_board(board);
+    }
 
-    let (max_width, max_height) = image.info().wh();
-    for height in 0..max_height {
-        for width in 0..max_width {
-            add_adjacent_rules(&mut ruleset, &image, width, height, rotate_rules);
-            update_frequencies(&mut frequencies, &image, width, height);
+    fn on_domain_change(&mut self, board: &Board, _cell: usize) {
+        if self.record_propagation {
+            self.write_board(board);
         }
     }
 
-    for tile_type in get_all_tile_types(&ruleset) {
-        if frequencies.get(&tile_type).is_none() {
-            frequencies.insert(tile_type, 0);
-        }
+    // shows the cell that ran out of choices before the board is backtracked or replaced
+    fn on_contradiction(&mut self, board: &Board, _contradiction: Contradiction) {
+        self.write_board(board);
     }
 
-    Some(Generation {
-        ruleset,
-        frequencies,
-    })
+    fn on_backtrack(&mut self, board: &Board, _restored: &[usize]) {
+        self.write_board(board);
+    }
 }
 
-#[derive(Clone, Debug)]
-struct PossibileTiles {
-    pub choices: HashSet<TileType>,
+fn generate_with_settings<T: Copy + Ord + Hash>(
+    generation: &Generation<T>,
+    settings: &Settings,
+) -> Result<Board, Error> {
+    get_solver(generation, settings).run()
 }
 
-#[derive(Clone, Debug)]
-enum Tile {
-    Revealed(TileType),
-    Hidden(PossibileTiles),
+// Holds every painted pixel to the tiles of its colour, so that only the unknown pixels are
+// generated.
+fn pin_painted_cells<T: Copy + Ord + Hash>(
+    solver: &mut Solver<'_, T>,
+    pixels: &[Vec<Option<TileType>>],
+    get_colour: impl Fn(T) -> TileType,
+) -> Result<(), String> {
+    let tiles = solver.generation().tiles();
+    for (height, row) in pixels.iter().enumerate() {
+        for (width, colour) in row.iter().enumerate() {
+            let Some(colour) = colour else {
+                continue;
+            };
+            let (matching, others): (Vec<T>, Vec<T>) =
+                tiles.iter().partition(|tile| get_colour(**tile) == *colour);
+
+            let position = (width, height, 0);
+            match matching.as_slice() {
+                [] => {
+                    let [red, green, blue] = colour.rgb;
+                    return Err(format!(
+                        "The colour {red},{green},{blue} at ({width}, {height}) is not in the sample."
+                    ));
+                }
+                [tile] => solver.pin(position, *tile),
+                _ => solver.ban(position, &others),
+            }
+            .map_err(|error| error.to_string())?;
+        }
+    }
+
+    Ok(())
 }
 
-fn choose_tile(possible_tiles: &PossibileTiles, frequencies: &HashMap<TileType, u32>) -> TileType {
-    if possible_tiles.choices.is_empty() {
-        return INVALID_TILE;
+// Solves a board and returns the colour of every cell, saving each frame of the collapse along
+// the way if a recording was asked for.
+fn solve_pixels<T: Copy + Ord + Hash>(
+    generation: &Generation<T>,
+    settings: &Settings,
+    get_colour: impl Fn(T) -> TileType,
+) -> Result<Vec<Vec<Option<TileType>>>, String> {
+    let mut solver = get_solver(generation, settings);
+    if let Some(pixels) = &settings.inpaint {
+        pin_painted_cells(&mut solver, pixels, &get_colour)?;
     }
 
-    let mut tile_choices = vec![];
-    for tile in &possible_tiles.choices {
-        let frequency = frequencies.get(tile).unwrap_or(&0);
-        for _ in 0..(*frequency) {
-            tile_choices.push(tile);
-        }
-    }
-    let mut rng = rand::thread_rng();
-    let distribution = Uniform::from(0..tile_choices.len());
-    let index = distribution.sample(&mut rng);
+    let Some(path) = &settings.record else {
+        let board = solver.run().map_err(|error| error.to_string())?;
+        return Ok(get_colours(&board, generation, get_colour));
+    };
 
-    **tile_choices.get(index).unwrap_or(&&INVALID_TILE)
+    let mut recording = Recording::create(path, settings, generation, &get_colour)
+        .map_err(|error| error.to_string())?;
+    recording.write_board(solver.board());
+    let board = solver.with_observer(&mut recording).run();
+    let frames = recording.finish().map_err(|error| error.to_string())?;
+    println!("Saved {frames} frames of the collapse to {path}.");
+
+    let board = board.map_err(|error| error.to_string())?;
+    Ok(get_colours(&board, generation, get_colour))
 }
 
-fn remove_choices(
-    source_tile: TileType,
-    direction: Direction,
-    ruleset: &HashSet<Rule>,
-    original_choices: &mut HashSet<TileType>,
-) {
-    let mut allowed_from_source = HashSet::<TileType>::new();
-    for rule in ruleset {
-        if rule.from == source_tile && rule.direction == direction {
-            allowed_from_source.insert(rule.to);
+fn parse_augmentation(args: &[String]) -> Result<Option<Augmentation>, String> {
+    let augmentation = Augmentation {
+        rotations: has_flag(args, "--rotations"),
+        reflections: has_flag(args, "--reflections"),
+        isotropic: has_flag(args, "--isotropic"),
+    };
+
+    match augmentation {
+        Augmentation {
+            rotations: false,
+            reflections: false,
+            isotropic: false,
+        } => Ok(None),
+        Augmentation {
+            isotropic: true,
+            rotations,
+            reflections,
+        } if rotations || reflections => {
+            Err("--isotropic cannot be combined with --rotations or --reflections.".to_owned())
         }
+        _ => Ok(Some(augmentation)),
     }
-
-    let all_tile_types = get_all_tile_types(ruleset);
-    let choices_to_remove = all_tile_types.difference(&allowed_from_source);
-    let collected_difference = choices_to_remove.collect::<HashSet<_>>();
-    let new_choices = original_choices
-        .difference(&collected_difference)
-        .collect::<HashSet<_>>();
-    *original_choices = new_choices;
 }
 
-fn update_possible_tiles(
-    board: &mut [Vec<Tile>],
-    ruleset: &HashSet<Rule>,
-    width: usize,
-    height: usize,
-    direction: Direction,
-) {
-    let source_tile = if let Some(row) = board.get(height) {
-        if let Some(&Tile::Revealed(tile)) = row.get(width) {
-            tile
-        } else {
-            return;
-        }
-    } else {
-        return;
-    };
+// Voxel boards are written to a MagicaVoxel file rather than printed.
+fn run_voxel_model(
+    file_path: &str,
+    settings: &Settings,
+    show_compatibility: bool,
+) -> Result<(), Error> {
+    let augmentation = settings.augmentation.unwrap_or_default();
+    let model = voxel_init(file_path, augmentation, settings.periodic_input)?;
+    if show_compatibility {
+        print_compatibility(&model.generation);
+    }
 
-    let (del_w, del_h) = direction.get_deltas();
+    let board = generate_with_settings(&model.generation, settings)?;
 
-    let new_w = del_w
-        .checked_add(unwrap_result_or_return!(i8::try_from(width)))
-        .unwrap_or(i8::MAX);
-    let new_h = del_h
-        .checked_add(unwrap_result_or_return!(i8::try_from(height)))
-        .unwrap_or(i8::MAX);
+    let output_path = settings.output.as_deref().unwrap_or("output.vox");
+    write_vox(&model.to_grid(&board)?, output_path)?;
+    println!("Saved the generated voxels to {output_path}.");
 
-    if new_w < 0 || new_h < 0 {
-        return;
-    }
+    Ok(())
+}
 
-    if let Some(row) = board.get_mut(unwrap_result_or_return!(usize::try_from(new_h))) {
-        if let Some(cell) = row.get_mut(unwrap_result_or_return!(usize::try_from(new_w))) {
-            match cell {
-                Tile::Hidden(possible_tiles) => {
-                    remove_choices(source_tile, direction, ruleset, &mut possible_tiles.choices);
-                }
-                Tile::Revealed(_) => {}
-            }
+// Learns rules from an image or tileset and solves a board with them, returning the colour of
+// every cell.
+fn generate_pixels(
+    file_name: &str,
+    file_path: &str,
+    settings: &Settings,
+    args: &[String],
+) -> Result<Vec<Vec<Option<TileType>>>, String> {
+    let show_compatibility = has_flag(args, "--print-compatibility");
+    let Settings {
+        overlapping,
+        periodic_input,
+        neighbourhood,
+        augmentation,
+        ..
+    } = *settings;
+
+    if file_name.ends_with(".tileset") {
+        // tiles take their orientations from their symmetry class instead
+        if augmentation.is_some() {
+            return Err("Symmetry flags do not apply to tilesets.".to_owned());
         }
+        let model = tileset_init(file_path, neighbourhood)
+            .map_err(|error| format!("Failed to load the provided tileset. {error}"))?;
+        if show_compatibility {
+            print_compatibility(&model.generation);
+        }
+
+        solve_pixels(&model.generation, settings, |tile| model.get_colour(tile))
+    } else if let Some(size) = overlapping {
+        let model = overlapping_init(
+            file_path,
+            size,
+            augmentation.unwrap_or_default(),
+            periodic_input,
+            neighbourhood,
+        )
+        .map_err(|error| format!("Failed to extract patterns from the provided file. {error}"))?;
+        if show_compatibility {
+            print_compatibility(&model.generation);
+        }
+
+        solve_pixels(&model.generation, settings, |pattern_id| {
+            model.get_colour(pattern_id)
+        })
+    } else {
+        let mut generation = generation_init(
+            file_path,
+            // learning every adjacency in every direction lets the tiny samples in `resources`
+            // fill large boards, so it stays the default
+            augmentation.unwrap_or(Augmentation::ISOTROPIC),
+            periodic_input,
+            neighbourhood,
+        )
+        .map_err(|error| {
+            format!("Failed to create generation rules based on the provided file. {error}")
+        })?;
+        apply_weight_overrides(&mut generation, args)?;
+        if show_compatibility {
+            print_compatibility(&generation);
+        }
+
+        solve_pixels(&generation, settings, |tile_type| tile_type)
     }
 }
 
-fn reveal(board: &mut [Vec<Tile>], generation: &Generation, width: usize, height: usize) {
-    if let Some(row) = board.get_mut(height) {
-        if let Some(tile) = row.get_mut(width) {
-            if let Tile::Hidden(possible_tiles) = tile {
-                let new_type = choose_tile(possible_tiles, &generation.frequencies);
-                *tile = Tile::Revealed(new_type);
+// Shows the board inline as an image if the terminal supports it, and as coloured text otherwise.
+fn print_board(pixels: &[Vec<Option<TileType>>], settings: &Settings) -> ExitCode {
+    let Some(protocol) = settings.graphics else {
+        print!(
+            "{}",
+            render_ansi(pixels, settings.neighbourhood, settings.colour_depth)
+        );
+        return ExitCode::SUCCESS;
+    };
 
-                for direction in Direction::iter() {
-                    update_possible_tiles(board, &generation.ruleset, width, height, direction);
-                }
-            }
+    match render_graphics(
+        pixels,
+        settings.neighbourhood,
+        settings.scale.unwrap_or(8),
+        protocol,
+    ) {
+        Ok(image) => {
+            print!("{image}");
+            ExitCode::SUCCESS
         }
-    };
+        Err(error) => {
+            println!("{error} Exiting.");
+            ExitCode::FAILURE
+        }
+    }
 }
 
-fn main() {
+fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
+    if has_flag(&args, "--help") || has_flag(&args, "-h") {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
     let Some(file_name) = args.get(1) else {
-        println!("Must pass an argument specifying the file to use. Exiting.");
-        return;
+        println!("Must pass an argument specifying the file to use.\n\n{USAGE}");
+        return ExitCode::FAILURE;
     };
     let file_path = format!("./resources/{file_name}");
 
-    let Some(generation) = generation_init(&file_path, true) else {
-        println!("Failed to create generation rules based on the provided file. Exiting.",);
-        return;
-    };
-
-    let mut board = vec![
-        vec![
-            Tile::Hidden(PossibileTiles {
-                choices: get_all_tile_types(&generation.ruleset)
-            });
-            20
-        ];
-        20
-    ];
-
-    let max_height = board.len();
-    let empty_row = &vec![];
-    let first_row = board.get(0).unwrap_or(empty_row);
-    let max_width = first_row.len();
-
-    for height in 0..max_height {
-        for width in 0..max_width {
-            reveal(&mut board, &generation, width, height);
+    let settings = match parse_settings(&args) {
+        Ok(settings) => settings,
+        Err(message) => {
+            println!("{message} Exiting.");
+            return ExitCode::FAILURE;
         }
+    };
+    println!("Seed: {}", settings.seed);
+
+    if has_extension(file_name, "vox") {
+        let show_compatibility = has_flag(&args, "--print-compatibility");
+        return match run_voxel_model(&file_path, &settings, show_compatibility) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                println!("{error} Exiting.");
+                ExitCode::FAILURE
+            }
+        };
     }
 
-    for height in 0..max_height {
-        for width in 0..max_width {
-            if let Some(row) = board.get(height) {
-                match row.get(width) {
-                    Some(&Tile::Revealed(tile)) => match tile {
-                        COAST_TILE => print!("\u{1f7e8}"),
-                        GRASS_TILE => print!("\u{1f7e9}"),
-                        WATER_TILE => print!("\u{1f7e6}"),
-                        _ => print!("\u{1f7e5}"),
-                    },
-                    _ => print!("\u{2b1c}"),
-                }
-            }
+    let pixels = match generate_pixels(file_name, &file_path, &settings, &args) {
+        Ok(pixels) => pixels,
+        Err(message) => {
+            println!("{message} Exiting.");
+            return ExitCode::FAILURE;
         }
-        println!();
+    };
+
+    let Some(output_path) = &settings.output else {
+        return print_board(&pixels, &settings);
+    };
+    if let Err(error) = write_image(
+        &pixels,
+        settings.neighbourhood,
+        output_path,
+        settings.scale.unwrap_or(1),
+    ) {
+        println!("{error} Exiting.");
+        return ExitCode::FAILURE;
     }
+    println!("Saved the generated map to {output_path}.");
+
+    ExitCode::SUCCESS
 }
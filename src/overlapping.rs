@@ -0,0 +1,394 @@
+use crate::direction::{Augmentation, Direction, Neighbourhood};
+use crate::error::Error;
+use crate::generation::Generation;
+use crate::rule::Rule;
+use crate::tile::{TileType, INVALID_TILE};
+use imgproc_rs::image::BaseImage;
+use imgproc_rs::io;
+use std::collections::{HashMap, HashSet};
+use std::iter;
+
+/// A square block of pixels cut from the sample, the tile of the overlapping model.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Pattern {
+    /// Width and height of the block.
+    pub size: usize,
+    /// The pixels, row by row.
+    pub pixels: Vec<TileType>,
+}
+
+impl Pattern {
+    /// Cuts the block whose top-left pixel is at the given position, wrapping around the edges of
+    /// a periodic sample.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the block does not fit in the sample or the sample has fewer than three channels.
+    pub fn from_image(
+        image: &dyn BaseImage<u8>,
+        width: u32,
+        height: u32,
+        size: u32,
+        periodic: bool,
+    ) -> Result<Self, Error> {
+        let (max_width, max_height) = image.info().wh();
+
+        let mut pixels = vec![];
+        for del_h in 0..size {
+            for del_w in 0..size {
+                let mut pixel_w = width.checked_add(del_w).ok_or(Error::CoordinateOverflow)?;
+                let mut pixel_h = height.checked_add(del_h).ok_or(Error::CoordinateOverflow)?;
+                if periodic {
+                    pixel_w = pixel_w.checked_rem(max_width).unwrap_or(pixel_w);
+                    pixel_h = pixel_h.checked_rem(max_height).unwrap_or(pixel_h);
+                }
+                if pixel_w >= max_width || pixel_h >= max_height {
+                    return Err(Error::CoordinateOverflow);
+                }
+
+                pixels.push(TileType::from_pixel(image, pixel_w, pixel_h)?);
+            }
+        }
+
+        Ok(Self {
+            size: usize::try_from(size).map_err(|_| Error::CoordinateOverflow)?,
+            pixels,
+        })
+    }
+
+    /// The pixel at the given position within the block.
+    #[must_use]
+    pub fn get(&self, width: usize, height: usize) -> Option<TileType> {
+        if width >= self.size || height >= self.size {
+            return None;
+        }
+
+        self.pixels
+            .get(height.checked_mul(self.size)?.checked_add(width)?)
+            .copied()
+    }
+
+    fn remap(&self, get_source: impl Fn(usize, usize) -> Option<(usize, usize)>) -> Option<Self> {
+        let mut pixels = vec![];
+        for height in 0..self.size {
+            for width in 0..self.size {
+                let (source_w, source_h) = get_source(width, height)?;
+                pixels.push(self.get(source_w, source_h)?);
+            }
+        }
+
+        Some(Self {
+            size: self.size,
+            pixels,
+        })
+    }
+
+    /// The block turned a quarter circle clockwise.
+    #[must_use]
+    pub fn rotate_clockwise(&self) -> Option<Self> {
+        let last = self.size.checked_sub(1)?;
+        self.remap(|width, height| Some((height, last.checked_sub(width)?)))
+    }
+
+    /// The block mirrored left to right.
+    #[must_use]
+    pub fn reflect_horizontally(&self) -> Option<Self> {
+        let last = self.size.checked_sub(1)?;
+        self.remap(|width, height| Some((last.checked_sub(width)?, height)))
+    }
+
+    /// The block mirrored top to bottom.
+    #[must_use]
+    pub fn reflect_vertically(&self) -> Option<Self> {
+        let last = self.size.checked_sub(1)?;
+        self.remap(|width, height| Some((width, last.checked_sub(height)?)))
+    }
+
+    /// The pattern itself followed by every rotated and mirrored copy the augmentation asks for,
+    /// at most the eight symmetries of a square. Symmetric patterns produce duplicates, which
+    /// count towards their frequency as in the original algorithm.
+    #[must_use]
+    pub fn get_symmetries(&self, augmentation: Augmentation) -> Vec<Self> {
+        let mut variants = vec![self.clone()];
+        if augmentation.reflections {
+            variants.extend(self.reflect_horizontally());
+        }
+        if augmentation.rotations {
+            // the vertical mirror is the half turn of the horizontal one, so the rotations of the
+            // pattern and of one mirror image already cover it
+            for variant in variants.clone() {
+                let rotations =
+                    iter::successors(variant.rotate_clockwise(), Self::rotate_clockwise);
+                variants.extend(rotations.take(3));
+            }
+        } else if augmentation.reflections {
+            variants.extend(self.reflect_vertically());
+        }
+
+        variants
+    }
+
+    /// Whether `other` can sit one cell away in `direction`, i.e. both patterns agree on every
+    /// pixel where they overlap.
+    #[must_use]
+    pub fn overlaps(&self, other: &Self, direction: Direction) -> bool {
+        let (del_w, del_h) = direction.get_opposite().get_deltas();
+
+        for height in 0..self.size {
+            for width in 0..self.size {
+                let (Some(other_w), Some(other_h)) = (
+                    width.checked_add_signed(isize::from(del_w)),
+                    height.checked_add_signed(isize::from(del_h)),
+                ) else {
+                    continue;
+                };
+                let Some(other_pixel) = other.get(other_w, other_h) else {
+                    continue;
+                };
+
+                if self.get(width, height) != Some(other_pixel) {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Refers to a pattern by its position in `OverlappingModel::patterns`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PatternId {
+    /// Position in `OverlappingModel::patterns`.
+    pub index: usize,
+}
+
+/// The patterns found in a sample and the rules for which of them overlap.
+#[derive(Debug)]
+pub struct OverlappingModel {
+    /// Every distinct pattern in the sample.
+    pub patterns: Vec<Pattern>,
+    /// The rules to solve boards of patterns with.
+    pub generation: Generation<PatternId>,
+}
+
+impl OverlappingModel {
+    /// Each cell is drawn with the top-left pixel of its pattern.
+    #[must_use]
+    pub fn get_colour(&self, pattern_id: PatternId) -> TileType {
+        self.patterns
+            .get(pattern_id.index)
+            .and_then(|pattern| pattern.pixels.first())
+            .copied()
+            .unwrap_or(INVALID_TILE)
+    }
+}
+
+/// Extracts every `size` by `size` pattern from the sample. A periodic sample also yields the
+/// patterns that wrap across its edges.
+///
+/// # Errors
+///
+/// Fails if the image cannot be read or has fewer than three channels, if the patterns are empty
+/// or larger than the sample, and for isotropic rules, hex grids or voxel neighbourhoods, which
+/// patterns cannot be matched in.
+pub fn overlapping_init(
+    input_path: &str,
+    size: u32,
+    augmentation: Augmentation,
+    periodic_input: bool,
+    neighbourhood: Neighbourhood,
+) -> Result<OverlappingModel, Error> {
+    // adjacency between patterns comes from their pixels, so it cannot be copied across directions
+    if augmentation.isotropic {
+        return Err(Error::Unsupported {
+            reason: "The overlapping model cannot learn isotropic rules",
+        });
+    }
+
+    // empty patterns have no pixels to render or to match against each other
+    if size == 0 {
+        return Err(Error::Unsupported {
+            reason: "The overlapping model needs patterns of at least one pixel",
+        });
+    }
+
+    match neighbourhood {
+        // patterns are square, so shifting one by a hex direction does not line its pixels up
+        Neighbourhood::Hexagonal => {
+            return Err(Error::Unsupported {
+                reason: "The overlapping model does not support hex grids",
+            })
+        }
+        // patterns are flat, so shifting one to the front or back would compare it with itself
+        Neighbourhood::Voxel => {
+            return Err(Error::Unsupported {
+                reason: "The overlapping model does not support voxel grids",
+            })
+        }
+        Neighbourhood::VonNeumann | Neighbourhood::Moore => {}
+    }
+
+    let mut patterns = Vec::<Pattern>::new();
+    let mut pattern_ids = HashMap::<Pattern, PatternId>::new();
+    let mut frequencies = HashMap::<PatternId, u32>::new();
+
+    let image = io::read(input_path).map_err(|error| Error::from_image(input_path, error))?;
+
+    let (max_width, max_height) = image.info().wh();
+    let last_positions = if periodic_input {
+        max_width.checked_sub(1).zip(max_height.checked_sub(1))
+    } else {
+        max_width
+            .checked_sub(size)
+            .zip(max_height.checked_sub(size))
+    };
+    let Some((last_width, last_height)) = last_positions else {
+        return Err(Error::Unsupported {
+            reason: "The patterns are larger than the sample",
+        });
+    };
+
+    for height in 0..=last_height {
+        for width in 0..=last_width {
+            let pattern = Pattern::from_image(&image, width, height, size, periodic_input)?;
+
+            for variant in pattern.get_symmetries(augmentation) {
+                let pattern_id = *pattern_ids.entry(variant.clone()).or_insert_with(|| {
+                    let index = patterns.len();
+                    patterns.push(variant);
+                    PatternId { index }
+                });
+
+                let frequency = frequencies.entry(pattern_id).or_insert(0);
+                *frequency = frequency.checked_add(1).unwrap_or(u32::MAX);
+            }
+        }
+    }
+
+    let mut ruleset = HashSet::<Rule<PatternId>>::new();
+    for (from_index, from) in patterns.iter().enumerate() {
+        for (to_index, to) in patterns.iter().enumerate() {
+            for direction in neighbourhood.get_directions() {
+                if from.overlaps(to, direction) {
+                    ruleset.insert(Rule::new(
+                        PatternId { index: from_index },
+                        PatternId { index: to_index },
+                        direction,
+                    ));
+                }
+            }
+        }
+    }
+
+    if ruleset.is_empty() {
+        return Err(Error::EmptyRuleset);
+    }
+
+    Ok(OverlappingModel {
+        patterns,
+        generation: Generation::new(&ruleset, &frequencies, neighbourhood),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 2x2 pattern whose pixels are numbered row by row
+    fn pattern(values: [u8; 4]) -> Pattern {
+        Pattern {
+            size: 2,
+            pixels: values
+                .iter()
+                .map(|value| TileType {
+                    rgb: [*value, *value, *value],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rotates_clockwise() {
+        assert_eq!(
+            pattern([1, 2, 3, 4]).rotate_clockwise(),
+            Some(pattern([3, 1, 4, 2]))
+        );
+    }
+
+    #[test]
+    fn four_rotations_restore_the_pattern() {
+        let original = pattern([1, 2, 3, 4]);
+        let rotated = iter::successors(Some(original.clone()), Pattern::rotate_clockwise).nth(4);
+        assert_eq!(rotated, Some(original));
+    }
+
+    #[test]
+    fn reflects() {
+        let original = pattern([1, 2, 3, 4]);
+        assert_eq!(original.reflect_horizontally(), Some(pattern([2, 1, 4, 3])));
+        assert_eq!(original.reflect_vertically(), Some(pattern([3, 4, 1, 2])));
+    }
+
+    #[test]
+    fn symmetries_are_the_eight_of_a_square() {
+        let augmentation = |rotations, reflections| Augmentation {
+            rotations,
+            reflections,
+            isotropic: false,
+        };
+        let original = pattern([1, 2, 3, 4]);
+        let count_distinct =
+            |variants: Vec<Pattern>| variants.into_iter().collect::<HashSet<_>>().len();
+
+        // an asymmetric pattern yields every symmetry exactly once
+        let all = original.get_symmetries(augmentation(true, true));
+        assert_eq!(all.len(), 8);
+        assert_eq!(count_distinct(all.clone()), 8);
+        assert!(original
+            .reflect_vertically()
+            .is_some_and(|mirrored| all.contains(&mirrored)));
+
+        assert_eq!(original.get_symmetries(augmentation(true, false)).len(), 4);
+        let mirrored = original.get_symmetries(augmentation(false, true));
+        assert_eq!(count_distinct(mirrored), 3);
+        assert_eq!(original.get_symmetries(Augmentation::default()), [original]);
+    }
+
+    #[test]
+    fn overlaps_where_shared_pixels_agree() {
+        let left = pattern([1, 2, 3, 4]);
+        let right = pattern([2, 5, 4, 6]);
+        assert!(left.overlaps(&right, Direction::Right));
+        assert!(right.overlaps(&left, Direction::Left));
+        assert!(!left.overlaps(&right, Direction::Left));
+        assert!(!left.overlaps(&right, Direction::Down));
+
+        let below = pattern([3, 4, 7, 8]);
+        assert!(left.overlaps(&below, Direction::Down));
+        assert!(below.overlaps(&left, Direction::Up));
+        assert!(left.overlaps(&pattern([4, 9, 9, 9]), Direction::DownRight));
+    }
+
+    #[test]
+    fn rejects_empty_patterns_and_voxel_grids() {
+        let init = |size, neighbourhood| {
+            overlapping_init(
+                "resources/beach.bmp",
+                size,
+                Augmentation::default(),
+                false,
+                neighbourhood,
+            )
+        };
+        assert!(matches!(
+            init(0, Neighbourhood::VonNeumann),
+            Err(Error::Unsupported { .. })
+        ));
+        assert!(matches!(
+            init(2, Neighbourhood::Voxel),
+            Err(Error::Unsupported { .. })
+        ));
+        assert!(init(2, Neighbourhood::VonNeumann).is_ok());
+    }
+}
@@ -0,0 +1,698 @@
+use crate::bitset::Bitset;
+use crate::board::{Board, Dimensions, Tile};
+use crate::direction::Neighbourhood;
+use crate::error::Error;
+use crate::generation::Generation;
+use crate::tile::{get_average_colour, TileType, INVALID_TILE};
+use core::hash::Hash;
+use gif::{Encoder, Frame, Repeat};
+use imgproc_rs::image::Image;
+use imgproc_rs::io;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use strum_macros::EnumString;
+
+/// The colour of the space left beside the shifted rows of a hex board.
+pub const HEX_MARGIN: TileType = TileType { rgb: [0, 0, 0] };
+
+// How many pixels the odd rows of a hex board are shifted right by, half a cell rounded down.
+fn get_hex_shift(neighbourhood: Neighbourhood, scale: u32) -> u32 {
+    if neighbourhood == Neighbourhood::Hexagonal {
+        scale.checked_div(2).unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+// The width and height in pixels of an image of the given number of cells.
+fn get_image_size(
+    cells_wide: usize,
+    cells_high: usize,
+    neighbourhood: Neighbourhood,
+    scale: u32,
+) -> Result<(u32, u32), Error> {
+    let to_length = |cells: usize| {
+        u32::try_from(cells)
+            .ok()
+            .and_then(|cells| cells.checked_mul(scale))
+            .ok_or(Error::CoordinateOverflow)
+    };
+    let width = to_length(cells_wide)?
+        .checked_add(get_hex_shift(neighbourhood, scale))
+        .ok_or(Error::CoordinateOverflow)?;
+
+    Ok((width, to_length(cells_high)?))
+}
+
+// The cells blown up so that each one covers a `scale` by `scale` block, row by row. Odd rows of
+// a hex board are shifted right by half a cell, leaving a margin at the start of those rows and
+// at the end of the others.
+struct ScaledImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<TileType>,
+}
+
+impl ScaledImage {
+    fn new(
+        pixels: &[Vec<Option<TileType>>],
+        neighbourhood: Neighbourhood,
+        scale: u32,
+    ) -> Result<Self, Error> {
+        let scale = scale.max(1);
+        let cells_wide = pixels.iter().map(Vec::len).max().unwrap_or(0);
+        let (width, height) = get_image_size(cells_wide, pixels.len(), neighbourhood, scale)?;
+        let row_length = usize::try_from(width).map_err(|_| Error::CoordinateOverflow)?;
+        let shift = usize::try_from(get_hex_shift(neighbourhood, scale))
+            .map_err(|_| Error::CoordinateOverflow)?;
+
+        let mut scaled_pixels = vec![];
+        for (height, row) in pixels.iter().enumerate() {
+            let mut scaled_row = vec![HEX_MARGIN; if height % 2 == 1 { shift } else { 0 }];
+            for width in 0..cells_wide {
+                let tile = row.get(width).copied().flatten().unwrap_or(INVALID_TILE);
+                for _ in 0..scale {
+                    scaled_row.push(tile);
+                }
+            }
+            scaled_row.resize(row_length, HEX_MARGIN);
+            for _ in 0..scale {
+                scaled_pixels.extend_from_slice(&scaled_row);
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels: scaled_pixels,
+        })
+    }
+
+    fn get_rgb_bytes(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|tile| tile.rgb).collect()
+    }
+
+    // Sixel terminals and GIF frames only have room for 256 colours, so images with more are
+    // reduced to the 256-colour palette.
+    fn with_limited_colours(mut self) -> Self {
+        if self.get_palette().len() > 256 {
+            for tile in &mut self.pixels {
+                tile.rgb = get_palette_rgb(get_palette_index(tile.rgb));
+            }
+        }
+        self
+    }
+
+    fn get_palette(&self) -> Vec<TileType> {
+        let colours = self.pixels.iter().copied().collect::<BTreeSet<TileType>>();
+        colours.into_iter().collect()
+    }
+}
+
+/// The colour of every cell of a board, row by row, for drawing it while it is being solved.
+///
+/// Revealed cells take the colour of their tile, and hidden cells the average colour of the
+/// tiles they may still become. Cells with no choices left have no colour.
+#[must_use]
+pub fn get_colours<T: Copy + Ord + Hash>(
+    board: &Board,
+    generation: &Generation<T>,
+    get_colour: impl Fn(T) -> TileType,
+) -> Vec<Vec<Option<TileType>>> {
+    let tile_colours = generation
+        .tiles
+        .iter()
+        .map(|tile| get_colour(*tile))
+        .collect::<Vec<TileType>>();
+    // most hidden cells share their choices with many others, especially early on
+    let mut averages = HashMap::<&Bitset, Option<TileType>>::new();
+
+    board
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|tile| match tile {
+                    Tile::Revealed(tile_index) => tile_colours.get(*tile_index).copied(),
+                    Tile::Hidden(possible_tiles) => {
+                        *averages.entry(&possible_tiles.choices).or_insert_with(|| {
+                            get_average_colour(
+                                possible_tiles
+                                    .choices
+                                    .iter()
+                                    .filter_map(|choice| tile_colours.get(choice).copied()),
+                            )
+                        })
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Saves the colour of every cell as an image, in the format given by the extension of `path`.
+///
+/// Each cell becomes a `scale` by `scale` block of pixels, and a scale of 0 is treated as 1. Cells
+/// without a colour are drawn as [`INVALID_TILE`]. Odd rows of a hex board are shifted right by
+/// half a cell, with [`HEX_MARGIN`] filling the space beside them. At a scale of 1 no row is
+/// shifted, so the image can be read back as a sample.
+///
+/// # Errors
+///
+/// Fails if the scaled image is too large to describe, or if the file cannot be written.
+pub fn write_image(
+    pixels: &[Vec<Option<TileType>>],
+    neighbourhood: Neighbourhood,
+    path: &str,
+    scale: u32,
+) -> Result<(), Error> {
+    let scaled = ScaledImage::new(pixels, neighbourhood, scale)?;
+    let image = Image::from_vec(
+        scaled.width,
+        scaled.height,
+        3,
+        false,
+        scaled.get_rgb_bytes(),
+    );
+    io::write(&image, path).map_err(|error| Error::from_image(path, error))
+}
+
+/// How many colours the terminal can show.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColourDepth {
+    /// Any 24-bit colour.
+    #[default]
+    TrueColour,
+    /// The 256-colour palette most terminals support.
+    Palette256,
+}
+
+impl ColourDepth {
+    /// Terminals with 24-bit colour advertise it through `COLORTERM`. Every other terminal gets the
+    /// 256-colour palette.
+    #[must_use]
+    pub fn from_env() -> Self {
+        match env::var("COLORTERM").as_deref() {
+            Ok("truecolor" | "24bit") => Self::TrueColour,
+            _ => Self::Palette256,
+        }
+    }
+}
+
+// The levels of each channel in the 6x6x6 colour cube at indices 16 to 231.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn get_distance(first: [u8; 3], second: [u8; 3]) -> u32 {
+    first
+        .iter()
+        .zip(second)
+        .map(|(first, second)| u32::from(first.abs_diff(second)).pow(2))
+        .sum()
+}
+
+// The closest entry of the 256-colour palette, either from the colour cube or the grey ramp at
+// indices 232 to 255. The first 16 colours vary between terminals, so they are never used.
+fn get_palette_index(rgb: [u8; 3]) -> u8 {
+    let nearest_level = |channel: u8| {
+        (0_u8..6)
+            .zip(CUBE_LEVELS)
+            .min_by_key(|(_, level)| level.abs_diff(channel))
+            .unwrap_or_default()
+    };
+    let [(red, red_level), (green, green_level), (blue, blue_level)] = rgb.map(nearest_level);
+    let cube_index = red
+        .saturating_mul(36)
+        .saturating_add(green.saturating_mul(6))
+        .saturating_add(blue)
+        .saturating_add(16);
+    let cube_distance = get_distance(rgb, [red_level, green_level, blue_level]);
+
+    // the grey ramp runs from 8 to 238 in steps of 10
+    let (grey, grey_level) = (0_u8..24)
+        .map(|step| (step, step.saturating_mul(10).saturating_add(8)))
+        .min_by_key(|(_, level)| get_distance(rgb, [*level; 3]))
+        .unwrap_or_default();
+    if get_distance(rgb, [grey_level; 3]) < cube_distance {
+        grey.saturating_add(232)
+    } else {
+        cube_index
+    }
+}
+
+fn get_background(tile: TileType, depth: ColourDepth) -> String {
+    let [red, green, blue] = tile.rgb;
+    match depth {
+        ColourDepth::TrueColour => format!("\u{1b}[48;2;{red};{green};{blue}m"),
+        ColourDepth::Palette256 => format!("\u{1b}[48;5;{}m", get_palette_index(tile.rgb)),
+    }
+}
+
+/// Draws the cells for a terminal using ANSI background colours.
+///
+/// Each cell is two spaces wide, so cells come out roughly square. Cells without a colour are
+/// shaded instead. Odd rows of a hex board are indented by half
+/// a cell, so that each row sits between the ones above and below it.
+#[must_use]
+pub fn render_ansi(
+    pixels: &[Vec<Option<TileType>>],
+    neighbourhood: Neighbourhood,
+    depth: ColourDepth,
+) -> String {
+    const RESET: &str = "\u{1b}[0m";
+    let mut output = String::new();
+
+    for (height, row) in pixels.iter().enumerate() {
+        if neighbourhood == Neighbourhood::Hexagonal && height % 2 == 1 {
+            output.push(' ');
+        }
+
+        // the background only needs setting when it changes along the row
+        let mut current = None;
+        for pixel in row {
+            if *pixel != current {
+                output.push_str(RESET);
+                if let Some(tile) = pixel {
+                    output.push_str(&get_background(*tile, depth));
+                }
+                current = *pixel;
+            }
+            output.push_str(if pixel.is_some() {
+                "  "
+            } else {
+                "\u{2591}\u{2591}"
+            });
+        }
+        output.push_str(RESET);
+        output.push('\n');
+    }
+
+    output
+}
+
+/// The protocols terminals use to show images inline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum GraphicsProtocol {
+    /// The graphics protocol of kitty, which `WezTerm` and Ghostty also understand.
+    Kitty,
+    /// DEC sixel graphics, understood by xterm, foot, mlterm and others.
+    Sixel,
+}
+
+impl GraphicsProtocol {
+    /// Guesses which protocol the terminal understands from its environment. Terminals rarely
+    /// advertise sixel support, so only the ones known to have it are recognised.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || matches!(program.as_str(), "WezTerm" | "ghostty")
+        {
+            Some(Self::Kitty)
+        } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+            Some(Self::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut output = String::new();
+
+    for group in bytes.chunks(3) {
+        let [first, second, third] = [0, 1, 2].map(|index| group.get(index).copied().unwrap_or(0));
+        let sextets = [
+            first >> 2,
+            (first & 0b11) << 4 | second >> 4,
+            (second & 0b1111) << 2 | third >> 6,
+            third & 0b11_1111,
+        ];
+        // a partial group of n bytes fills n + 1 characters, and the rest are padding
+        for (position, sextet) in sextets.into_iter().enumerate() {
+            if position <= group.len() {
+                let character = BASE64_ALPHABET.get(usize::from(sextet)).copied();
+                output.push(char::from(character.unwrap_or(b'A')));
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+// Kitty limits each escape sequence to 4096 bytes of payload.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+fn encode_kitty(image: &ScaledImage) -> String {
+    let payload = encode_base64(&image.get_rgb_bytes());
+    let chunks = payload
+        .as_bytes()
+        .chunks(KITTY_CHUNK_SIZE)
+        .collect::<Vec<&[u8]>>();
+
+    let escapes = chunks.iter().enumerate().map(|(index, chunk)| {
+        let more = u8::from(index.saturating_add(1) < chunks.len());
+        // only the first chunk describes the image, the rest just continue its data
+        let control = if index == 0 {
+            format!("a=T,f=24,s={},v={},m={more}", image.width, image.height)
+        } else {
+            format!("m={more}")
+        };
+        let data = core::str::from_utf8(chunk).unwrap_or_default();
+        format!("\u{1b}_G{control};{data}\u{1b}\\")
+    });
+
+    let mut output = String::new();
+    output.extend(escapes);
+    output.push('\n');
+
+    output
+}
+
+fn get_palette_rgb(index: u8) -> [u8; 3] {
+    if let Some(step) = index.checked_sub(232) {
+        return [step.saturating_mul(10).saturating_add(8); 3];
+    }
+
+    let cube_index = index.saturating_sub(16);
+    [cube_index / 36, cube_index / 6 % 6, cube_index % 6]
+        .map(|level| CUBE_LEVELS.get(usize::from(level)).copied().unwrap_or(0))
+}
+
+// Sixel images draw six rows at a time, one colour register per pass.
+const SIXEL_BAND: u8 = 6;
+
+fn push_sixel_run(output: &mut String, sixel: u8, count: usize) {
+    let character = char::from(sixel.saturating_add(63));
+    if count > 3 {
+        output.push('!');
+        output.push_str(&count.to_string());
+        output.push(character);
+    } else {
+        for _ in 0..count {
+            output.push(character);
+        }
+    }
+}
+
+fn encode_sixel(image: &ScaledImage) -> String {
+    let colours = image.get_palette();
+    let registers = colours
+        .iter()
+        .enumerate()
+        .map(|(register, tile)| (*tile, register))
+        .collect::<HashMap<TileType, usize>>();
+
+    let mut output = format!("\u{1b}Pq\"1;1;{};{}", image.width, image.height);
+    // colours are given as percentages of each channel
+    let definitions = colours.iter().enumerate().map(|(register, tile)| {
+        let [red, green, blue] = tile.rgb.map(|channel| u16::from(channel) * 100 / 255);
+        format!("#{register};2;{red};{green};{blue}")
+    });
+    output.extend(definitions);
+
+    let get_pixel = |width: u32, height: u32| {
+        let index = height.checked_mul(image.width)?.checked_add(width)?;
+        image.pixels.get(usize::try_from(index).ok()?).copied()
+    };
+    for band_top in (0..image.height).step_by(usize::from(SIXEL_BAND)) {
+        let band = band_top
+            ..image
+                .height
+                .min(band_top.saturating_add(u32::from(SIXEL_BAND)));
+        // each pass paints the pixels of one colour in the band, returning to its start with `$`
+        let mut band_colours = BTreeSet::new();
+        for height in band.clone() {
+            for width in 0..image.width {
+                band_colours.extend(get_pixel(width, height));
+            }
+        }
+
+        for colour in band_colours {
+            output.push('#');
+            output.push_str(
+                &registers
+                    .get(&colour)
+                    .copied()
+                    .unwrap_or_default()
+                    .to_string(),
+            );
+            let mut run = (0_u8, 0_usize);
+            for width in 0..image.width {
+                let sixel = band.clone().zip(0_u8..).fold(0_u8, |sixel, (height, bit)| {
+                    if get_pixel(width, height) == Some(colour) {
+                        sixel | 1 << bit
+                    } else {
+                        sixel
+                    }
+                });
+                if sixel == run.0 {
+                    run.1 = run.1.saturating_add(1);
+                } else {
+                    push_sixel_run(&mut output, run.0, run.1);
+                    run = (sixel, 1);
+                }
+            }
+            push_sixel_run(&mut output, run.0, run.1);
+            output.push('$');
+        }
+        output.push('-');
+    }
+    output.push_str("\u{1b}\\\n");
+
+    output
+}
+
+/// Draws the cells as an image shown inline by the terminal, with each cell a `scale` by `scale`
+/// block of pixels.
+///
+/// Cells without a colour are drawn as [`INVALID_TILE`], and hex boards are offset as in
+/// [`write_image`].
+///
+/// # Errors
+///
+/// Fails if the scaled image is too large to describe.
+pub fn render_graphics(
+    pixels: &[Vec<Option<TileType>>],
+    neighbourhood: Neighbourhood,
+    scale: u32,
+    protocol: GraphicsProtocol,
+) -> Result<String, Error> {
+    let image = ScaledImage::new(pixels, neighbourhood, scale)?;
+
+    Ok(match protocol {
+        GraphicsProtocol::Kitty => encode_kitty(&image),
+        GraphicsProtocol::Sixel => encode_sixel(&image.with_limited_colours()),
+    })
+}
+
+/// Writes boards as the frames of an animated GIF that loops forever.
+///
+/// Each frame has its own palette, so colours are only reduced in frames with more than 256 of
+/// them. Hex boards are offset as in [`write_image`]. The animation is complete once
+/// [`GifWriter::finish`] is called.
+pub struct GifWriter {
+    encoder: Encoder<BufWriter<File>>,
+    path: String,
+    neighbourhood: Neighbourhood,
+    scale: u32,
+    delay: u16,
+}
+
+impl GifWriter {
+    /// Creates the file at `path` for frames of boards with the given size, with each cell a
+    /// `scale` by `scale` block of pixels and `delay` hundredths of a second between frames.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a frame would be larger than a GIF can describe, or if the file cannot be created.
+    pub fn create(
+        path: &str,
+        dimensions: Dimensions,
+        neighbourhood: Neighbourhood,
+        scale: u32,
+        delay: u16,
+    ) -> Result<Self, Error> {
+        let scale = scale.max(1);
+        let (width, height) =
+            get_image_size(dimensions.width, dimensions.height, neighbourhood, scale)?;
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(Error::CoordinateOverflow);
+        };
+        let to_error = |message: String| Error::Io {
+            path: path.to_owned(),
+            message,
+        };
+
+        let file = File::create(path).map_err(|error| to_error(error.to_string()))?;
+        let mut encoder = Encoder::new(BufWriter::new(file), width, height, &[])
+            .map_err(|error| to_error(error.to_string()))?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|error| to_error(error.to_string()))?;
+
+        Ok(Self {
+            encoder,
+            path: path.to_owned(),
+            neighbourhood,
+            scale,
+            delay,
+        })
+    }
+
+    fn to_error(&self, message: String) -> Error {
+        Error::Io {
+            path: self.path.clone(),
+            message,
+        }
+    }
+
+    /// Appends the cells as the next frame. Cells without a colour are drawn as [`INVALID_TILE`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the frame is larger than a GIF can describe, or if the file cannot be written.
+    pub fn write_frame(&mut self, pixels: &[Vec<Option<TileType>>]) -> Result<(), Error> {
+        let image =
+            ScaledImage::new(pixels, self.neighbourhood, self.scale)?.with_limited_colours();
+        let (Ok(width), Ok(height)) = (u16::try_from(image.width), u16::try_from(image.height))
+        else {
+            return Err(Error::CoordinateOverflow);
+        };
+
+        let palette = image.get_palette();
+        let indices = palette
+            .iter()
+            .zip(0_u8..=u8::MAX)
+            .map(|(tile, index)| (*tile, index))
+            .collect::<HashMap<TileType, u8>>();
+        let frame_pixels = image
+            .pixels
+            .iter()
+            .map(|tile| indices.get(tile).copied().unwrap_or_default())
+            .collect::<Vec<u8>>();
+        let palette_bytes = palette
+            .iter()
+            .flat_map(|tile| tile.rgb)
+            .collect::<Vec<u8>>();
+
+        let mut frame =
+            Frame::from_palette_pixels(width, height, &frame_pixels, &palette_bytes, None);
+        frame.delay = self.delay;
+        self.encoder
+            .write_frame(&frame)
+            .map_err(|error| self.to_error(error.to_string()))
+    }
+
+    /// Ends the animation and flushes it to the file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be written.
+    pub fn finish(self) -> Result<(), Error> {
+        let Self { encoder, path, .. } = self;
+        encoder
+            .into_inner()
+            .and_then(|mut writer| writer.flush())
+            .map_err(|error| Error::Io {
+                path,
+                message: error.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: TileType = TileType { rgb: [255, 0, 0] };
+    const BLUE: TileType = TileType { rgb: [0, 0, 255] };
+
+    #[test]
+    fn scales_each_cell_to_a_block() -> Result<(), Error> {
+        let pixels = [vec![Some(RED), None]];
+        let image = ScaledImage::new(&pixels, Neighbourhood::VonNeumann, 2)?;
+
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(
+            image.pixels,
+            [RED, RED, INVALID_TILE, INVALID_TILE].repeat(2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shifts_odd_hex_rows_by_half_a_cell() -> Result<(), Error> {
+        let pixels = [vec![Some(RED)], vec![Some(BLUE)]];
+        let image = ScaledImage::new(&pixels, Neighbourhood::Hexagonal, 4)?;
+
+        assert_eq!((image.width, image.height), (6, 8));
+        let mut expected = [RED, RED, RED, RED, HEX_MARGIN, HEX_MARGIN].repeat(4);
+        expected.extend([HEX_MARGIN, HEX_MARGIN, BLUE, BLUE, BLUE, BLUE].repeat(4));
+        assert_eq!(image.pixels, expected);
+
+        // a single pixel has no half to shift by, so samples read back unchanged
+        let unscaled = ScaledImage::new(&pixels, Neighbourhood::Hexagonal, 1)?;
+        assert_eq!(unscaled.pixels, [RED, BLUE]);
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_base64_with_padding() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode_base64(&[0xff, 0xfe, 0x00]), "//4A");
+    }
+
+    #[test]
+    fn encodes_kitty_images_as_raw_rgb() -> Result<(), Error> {
+        let image = ScaledImage::new(&[vec![Some(RED)]], Neighbourhood::VonNeumann, 1)?;
+        assert_eq!(
+            encode_kitty(&image),
+            "\u{1b}_Ga=T,f=24,s=1,v=1,m=0;/wAA\u{1b}\\\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compresses_sixel_runs() {
+        let mut output = String::new();
+        push_sixel_run(&mut output, 1, 3);
+        push_sixel_run(&mut output, 0, 0);
+        push_sixel_run(&mut output, 63, 12);
+        assert_eq!(output, "@@@!12~");
+    }
+
+    #[test]
+    fn encodes_sixel_colours_one_pass_each() -> Result<(), Error> {
+        let image = ScaledImage::new(&[vec![Some(RED), Some(BLUE)]], Neighbourhood::VonNeumann, 1)?;
+        assert_eq!(
+            encode_sixel(&image),
+            "\u{1b}Pq\"1;1;2;1#0;2;0;0;100#1;2;100;0;0#0?@$#1@?$-\u{1b}\\\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_sixel_rows_in_bands_of_six() -> Result<(), Error> {
+        let pixels = vec![vec![Some(RED)]; 7];
+        let image = ScaledImage::new(&pixels, Neighbourhood::VonNeumann, 1)?;
+        assert_eq!(
+            encode_sixel(&image),
+            "\u{1b}Pq\"1;1;1;7#0;2;100;0;0#0~$-#0@$-\u{1b}\\\n"
+        );
+        Ok(())
+    }
+}
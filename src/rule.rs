@@ -0,0 +1,35 @@
+use crate::direction::Direction;
+use crate::tile::TileType;
+
+/// Allows `to` to sit one cell away from `from` in `direction`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Rule<T = TileType> {
+    /// The tile the rule is seen from.
+    pub from: T,
+    /// The tile allowed next to it.
+    pub to: T,
+    /// The step leading from `from` to `to`.
+    pub direction: Direction,
+}
+
+impl<T: Copy> Rule<T> {
+    /// Allows `to` one step from `from` in `direction`.
+    #[must_use]
+    pub const fn new(from: T, to: T, direction: Direction) -> Self {
+        Self {
+            from,
+            to,
+            direction,
+        }
+    }
+
+    /// The same adjacency seen from `to`, which every rule needs to be enforced both ways.
+    #[must_use]
+    pub const fn reverse(from: T, to: T, direction: Direction) -> Self {
+        Self {
+            from: to,
+            to: from,
+            direction: direction.get_opposite(),
+        }
+    }
+}
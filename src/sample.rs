@@ -0,0 +1,176 @@
+use crate::direction::{Augmentation, Direction, Neighbourhood};
+use crate::error::Error;
+use crate::generation::{get_all_tile_types, Generation};
+use crate::rule::Rule;
+use crate::tile::TileType;
+use core::hash::Hash;
+use imgproc_rs::image::BaseImage;
+use imgproc_rs::io;
+use std::collections::{HashMap, HashSet};
+
+// Steps from `position` by `delta` within a sample of the given length, wrapping around the
+// edges if the sample is periodic.
+pub fn offset_in_sample(position: u32, delta: i8, length: u32, periodic: bool) -> Option<u32> {
+    if !periodic {
+        return position
+            .checked_add_signed(i32::from(delta))
+            .filter(|new_position| *new_position < length);
+    }
+
+    let new_position = i64::from(position)
+        .checked_add(i64::from(delta))?
+        .checked_rem_euclid(i64::from(length))?;
+    u32::try_from(new_position).ok()
+}
+
+fn add_adjacent_rules(
+    ruleset: &mut HashSet<Rule>,
+    image: &dyn BaseImage<u8>,
+    width: u32,
+    height: u32,
+    augmentation: Augmentation,
+    periodic_input: bool,
+    neighbourhood: Neighbourhood,
+) -> Result<(), Error> {
+    let (max_width, max_height) = image.info().wh();
+
+    let from = TileType::from_pixel(image, width, height)?;
+
+    for direction in neighbourhood.get_directions() {
+        let (del_w, del_h) = neighbourhood.get_deltas(direction, height % 2 == 1);
+
+        let (Some(new_w), Some(new_h)) = (
+            offset_in_sample(width, del_w, max_width, periodic_input),
+            offset_in_sample(height, del_h, max_height, periodic_input),
+        ) else {
+            continue;
+        };
+
+        let to = TileType::from_pixel(image, new_w, new_h)?;
+        insert_symmetric_rules(ruleset, from, to, direction, neighbourhood, augmentation);
+    }
+
+    Ok(())
+}
+
+// Adds the rule letting `to` sit in `direction` from `from`, along with every orientation of it
+// the augmentation asks for. Tiles are single colours, so only the direction changes.
+pub fn insert_symmetric_rules<T: Copy + Ord + Hash>(
+    ruleset: &mut HashSet<Rule<T>>,
+    from: T,
+    to: T,
+    direction: Direction,
+    neighbourhood: Neighbourhood,
+    augmentation: Augmentation,
+) {
+    for symmetric_direction in neighbourhood.get_symmetric_directions(direction, augmentation) {
+        ruleset.insert(Rule::new(from, to, symmetric_direction));
+        ruleset.insert(Rule::reverse(from, to, symmetric_direction));
+    }
+}
+
+fn update_frequencies(
+    frequencies: &mut HashMap<TileType, u32>,
+    image: &dyn BaseImage<u8>,
+    width: u32,
+    height: u32,
+) -> Result<(), Error> {
+    let tile = TileType::from_pixel(image, width, height)?;
+
+    let frequency = frequencies.entry(tile).or_insert(0);
+    *frequency = frequency.checked_add(1).unwrap_or(u32::MAX);
+    Ok(())
+}
+
+/// Learns adjacency rules and tile frequencies from a sample image. With `periodic_input`, the
+/// sample is treated as tileable, so pixels on opposite edges are neighbours.
+///
+/// # Errors
+///
+/// Fails if the image cannot be read, has fewer than three channels, or is too small for any two
+/// pixels to be neighbours, and for periodic hex samples with an odd number of rows.
+pub fn generation_init(
+    input_path: &str,
+    augmentation: Augmentation,
+    periodic_input: bool,
+    neighbourhood: Neighbourhood,
+) -> Result<Generation, Error> {
+    let mut ruleset = HashSet::<Rule>::new();
+    let mut frequencies = HashMap::<TileType, u32>::new();
+
+    let image = io::read(input_path).map_err(|error| Error::from_image(input_path, error))?;
+
+    let (max_width, max_height) = image.info().wh();
+    // odd rows are shifted half a cell, so the top and bottom rows of an odd-height sample do not fit
+    if periodic_input && neighbourhood == Neighbourhood::Hexagonal && max_height % 2 == 1 {
+        return Err(Error::Unsupported {
+            reason: "A periodic hex sample needs an even number of rows",
+        });
+    }
+
+    for height in 0..max_height {
+        for width in 0..max_width {
+            add_adjacent_rules(
+                &mut ruleset,
+                &image,
+                width,
+                height,
+                augmentation,
+                periodic_input,
+                neighbourhood,
+            )?;
+            update_frequencies(&mut frequencies, &image, width, height)?;
+        }
+    }
+    if ruleset.is_empty() {
+        return Err(Error::EmptyRuleset);
+    }
+
+    for tile_type in get_all_tile_types(&ruleset) {
+        frequencies.entry(tile_type).or_insert(0);
+    }
+
+    Ok(Generation::new(&ruleset, &frequencies, neighbourhood))
+}
+
+/// Reads the colour of every pixel of an image, row by row.
+///
+/// # Errors
+///
+/// Fails if the image cannot be read or has fewer than three channels.
+pub fn read_image(path: &str) -> Result<Vec<Vec<TileType>>, Error> {
+    let image = io::read(path).map_err(|error| Error::from_image(path, error))?;
+
+    let (max_width, max_height) = image.info().wh();
+    (0..max_height)
+        .map(|height| {
+            (0..max_width)
+                .map(|width| TileType::from_pixel(&image, width, height))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periodic_hex_samples_need_an_even_number_of_rows() {
+        let init = |input_path| {
+            generation_init(
+                input_path,
+                Augmentation::default(),
+                true,
+                Neighbourhood::Hexagonal,
+            )
+        };
+        // 3 by 3 pixels
+        assert!(matches!(
+            init("resources/beach.bmp"),
+            Err(Error::Unsupported { .. })
+        ));
+        // 6 by 6 pixels
+        assert!(init("resources/sparse_beach.bmp").is_ok());
+    }
+}
@@ -0,0 +1,1373 @@
+use crate::bitset::Bitset;
+use crate::board::{get_weight, Board, Contradiction, Dimensions, PossibleTiles, Tile, Wrapping};
+use crate::direction::{Direction, Neighbourhood};
+use crate::error::Error;
+use crate::generation::Generation;
+use crate::tile::TileType;
+use core::cmp::Ordering;
+use core::hash::Hash;
+use core::mem;
+use rand::distributions::{Distribution, Uniform};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::{BTreeSet, BinaryHeap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+fn choose_tile(
+    possible_tiles: &PossibleTiles,
+    weights: &[f64],
+    rng: &mut impl Rng,
+) -> Option<usize> {
+    let choices = possible_tiles.choices.iter().collect::<Vec<usize>>();
+    let cumulative_weights = choices
+        .iter()
+        .scan(0.0, |total, tile| {
+            *total += get_weight(weights, *tile);
+            Some(*total)
+        })
+        .collect::<Vec<f64>>();
+    let total = cumulative_weights.last().copied().unwrap_or(0.0);
+
+    if total > 0.0 && total.is_finite() {
+        let target = rng.gen_range(0.0..total);
+        let position = cumulative_weights.partition_point(|weight| *weight <= target);
+        return choices.get(position).copied();
+    }
+
+    // every remaining choice has a zero weight, or the weights are too large to add up, so pick
+    // any of them
+    if choices.is_empty() {
+        return None;
+    }
+    // `usize` ranges draw a different number of bits on 32 and 64-bit targets, so the index is
+    // sampled as a `u64` to give the same choice everywhere
+    let distribution = Uniform::from(0..u64::try_from(choices.len()).ok()?);
+    choices
+        .get(usize::try_from(distribution.sample(rng)).ok()?)
+        .copied()
+}
+
+fn remove_choices<T: Copy + Ord + Hash>(
+    source_tiles: &Bitset,
+    direction: Direction,
+    generation: &Generation<T>,
+    possible_tiles: &mut PossibleTiles,
+) -> Bitset {
+    let mut allowed_from_source = Bitset::new(generation.tiles.len());
+    for tile in source_tiles.iter() {
+        if let Some(mask) = generation.get_mask(tile, direction) {
+            allowed_from_source.union_with(mask);
+        }
+    }
+
+    possible_tiles.restrict(&allowed_from_source, &generation.weights)
+}
+
+#[derive(Debug)]
+enum Change {
+    Removed {
+        cell: usize,
+        tile_index: usize,
+    },
+    Revealed {
+        cell: usize,
+        possible_tiles: PossibleTiles,
+    },
+}
+
+// The changes made to the board since the oldest decision that can still be undone, so that
+// backtracking restores the board without keeping a copy of it for every decision.
+#[derive(Debug, Default)]
+struct Trail {
+    pub enabled: bool,
+    pub changes: Vec<Change>,
+}
+
+impl Trail {
+    pub fn removed(&mut self, cell: usize, removed: &Bitset) {
+        if self.enabled {
+            self.changes.extend(
+                removed
+                    .iter()
+                    .map(|tile_index| Change::Removed { cell, tile_index }),
+            );
+        }
+    }
+
+    pub fn revealed(&mut self, cell: usize, possible_tiles: PossibleTiles) {
+        if self.enabled {
+            self.changes.push(Change::Revealed {
+                cell,
+                possible_tiles,
+            });
+        }
+    }
+
+    // Reverts changes, newest first, until only `length` of them are left. Returns the cells that
+    // were restored, in index order.
+    pub fn undo(&mut self, board: &mut Board, weights: &[f64], length: usize) -> Vec<usize> {
+        let mut restored = BTreeSet::new();
+        while self.changes.len() > length {
+            match self.changes.pop() {
+                Some(Change::Removed { cell, tile_index }) => {
+                    if let Some(Tile::Hidden(possible_tiles)) = board.get_mut(cell) {
+                        possible_tiles.insert(tile_index, weights);
+                        restored.insert(cell);
+                    }
+                }
+                Some(Change::Revealed {
+                    cell,
+                    possible_tiles,
+                }) => {
+                    if let Some(tile) = board.get_mut(cell) {
+                        *tile = Tile::Hidden(possible_tiles);
+                        restored.insert(cell);
+                    }
+                }
+                None => break,
+            }
+        }
+
+        restored.into_iter().collect()
+    }
+}
+
+// Narrows the neighbour in `direction` to the tiles supported by the source cell's domain,
+// returning the neighbour if its choices shrank.
+fn update_possible_tiles<T: Copy + Ord + Hash>(
+    board: &mut Board,
+    generation: &Generation<T>,
+    cell: usize,
+    direction: Direction,
+    trail: &mut Trail,
+) -> Result<Option<usize>, Contradiction> {
+    let Some(source_tiles) = board.get_domain(generation.tiles.len(), cell) else {
+        return Ok(None);
+    };
+    let Some(neighbour) = board.get_neighbour(cell, direction) else {
+        return Ok(None);
+    };
+
+    let Some(Tile::Hidden(possible_tiles)) = board.get_mut(neighbour) else {
+        return Ok(None);
+    };
+    let removed = remove_choices(&source_tiles, direction, generation, possible_tiles);
+    if removed.is_empty() {
+        return Ok(None);
+    }
+    trail.removed(neighbour, &removed);
+
+    if possible_tiles.choices.is_empty() {
+        Err(board.get_contradiction(neighbour))
+    } else {
+        Ok(Some(neighbour))
+    }
+}
+
+/// Receives the events of a [`Solver`] as it works through a board.
+///
+/// Every method does nothing by default, so an observer only implements the events it needs.
+pub trait Observer {
+    /// A hidden cell was collapsed to a single tile, and the change has propagated.
+    fn on_collapse(&mut self, _board: &Board, _cell: usize) {}
+
+    /// The choices of a hidden cell narrowed, either while propagating or because backtracking
+    /// ruled a tile out.
+    fn on_domain_change(&mut self, _board: &Board, _cell: usize) {}
+
+    /// A cell ran out of choices. The solver then backtracks, starts a fresh board or gives up.
+    fn on_contradiction(&mut self, _board: &Board, _contradiction: Contradiction) {}
+
+    /// Backtracking undid a decision, giving the `restored` cells back the choices they had
+    /// before it, in index order. Revealed cells among them are hidden again. Ruling the undone
+    /// choice out afterwards is reported through [`Observer::on_domain_change`].
+    fn on_backtrack(&mut self, _board: &Board, _restored: &[usize]) {}
+
+    /// Every cell of the board is revealed.
+    fn on_completion(&mut self, _board: &Board) {}
+}
+
+impl Observer for () {}
+
+impl<O: Observer + ?Sized> Observer for &mut O {
+    fn on_collapse(&mut self, board: &Board, cell: usize) {
+        (**self).on_collapse(board, cell);
+    }
+
+    fn on_domain_change(&mut self, board: &Board, cell: usize) {
+        (**self).on_domain_change(board, cell);
+    }
+
+    fn on_contradiction(&mut self, board: &Board, contradiction: Contradiction) {
+        (**self).on_contradiction(board, contradiction);
+    }
+
+    fn on_backtrack(&mut self, board: &Board, restored: &[usize]) {
+        (**self).on_backtrack(board, restored);
+    }
+
+    fn on_completion(&mut self, board: &Board) {
+        (**self).on_completion(board);
+    }
+}
+
+// Removes unsupported choices across the board until no domain changes, starting from the
+// given cell. Returns every cell whose choices shrank.
+fn propagate<T: Copy + Ord + Hash>(
+    board: &mut Board,
+    generation: &Generation<T>,
+    cell: usize,
+    observer: &mut impl Observer,
+    trail: &mut Trail,
+) -> Result<Vec<usize>, Contradiction> {
+    let mut queue = VecDeque::from([cell]);
+    let mut queued = HashSet::from([cell]);
+    let mut changed_cells = vec![];
+
+    while let Some(cell) = queue.pop_front() {
+        queued.remove(&cell);
+
+        for direction in generation.neighbourhood.get_directions() {
+            if let Some(changed) = update_possible_tiles(board, generation, cell, direction, trail)?
+            {
+                observer.on_domain_change(board, changed);
+                changed_cells.push(changed);
+                if queued.insert(changed) {
+                    queue.push_back(changed);
+                }
+            }
+        }
+    }
+
+    Ok(changed_cells)
+}
+
+fn reveal<T: Copy + Ord + Hash>(
+    board: &mut Board,
+    generation: &Generation<T>,
+    cell: usize,
+    tile_index: usize,
+    observer: &mut impl Observer,
+    trail: &mut Trail,
+) -> Result<Vec<usize>, Contradiction> {
+    let Some(tile) = board.get_mut(cell) else {
+        return Ok(vec![]);
+    };
+    if let Tile::Hidden(possible_tiles) = mem::replace(tile, Tile::Revealed(tile_index)) {
+        trail.revealed(cell, possible_tiles);
+    }
+
+    propagate(board, generation, cell, observer, trail)
+}
+
+fn ban<T: Copy + Ord + Hash>(
+    board: &mut Board,
+    generation: &Generation<T>,
+    cell: usize,
+    tile_index: usize,
+    observer: &mut impl Observer,
+    trail: &mut Trail,
+) -> Result<Vec<usize>, Contradiction> {
+    let Some(Tile::Hidden(possible_tiles)) = board.get_mut(cell) else {
+        return Ok(vec![]);
+    };
+
+    possible_tiles.remove(tile_index, &generation.weights);
+    trail.removed(cell, &Bitset::single(generation.tiles.len(), tile_index));
+    if possible_tiles.choices.is_empty() {
+        return Err(board.get_contradiction(cell));
+    }
+    observer.on_domain_change(board, cell);
+
+    let mut changed_cells = propagate(board, generation, cell, observer, trail)?;
+    changed_cells.push(cell);
+    Ok(changed_cells)
+}
+
+// Small enough to only ever reorder cells whose entropies are practically equal.
+const ENTROPY_NOISE: f64 = 1e-6;
+
+#[derive(Clone, Copy, Debug)]
+struct Candidate {
+    pub entropy: f64,
+    pub priority: f64,
+    pub cell: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed, so that `BinaryHeap` pops the lowest priority first.
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then_with(|| other.cell.cmp(&self.cell))
+    }
+}
+
+// Hidden cells ordered by entropy. Entries go stale when a cell's choices shrink, so a cell is
+// pushed again on every change and outdated entries are skipped when popping.
+#[derive(Clone, Debug, Default)]
+struct EntropyQueue {
+    pub heap: BinaryHeap<Candidate>,
+}
+
+impl EntropyQueue {
+    pub fn from_board(board: &Board, rng: &mut impl Rng) -> Self {
+        let mut queue = Self::default();
+        for cell in 0..board.cells.len() {
+            queue.push(board, cell, rng);
+        }
+        queue
+    }
+
+    pub fn push(&mut self, board: &Board, cell: usize, rng: &mut impl Rng) {
+        if let Some(Tile::Hidden(possible_tiles)) = board.get(cell) {
+            // a little noise breaks ties randomly so the collapse order has no directional bias
+            self.heap.push(Candidate {
+                entropy: possible_tiles.entropy,
+                priority: rng
+                    .gen::<f64>()
+                    .mul_add(ENTROPY_NOISE, possible_tiles.entropy),
+                cell,
+            });
+        }
+    }
+
+    pub fn pop(&mut self, board: &Board) -> Option<usize> {
+        while let Some(candidate) = self.heap.pop() {
+            if let Some(Tile::Hidden(possible_tiles)) = board.get(candidate.cell) {
+                if possible_tiles.entropy.total_cmp(&candidate.entropy) == Ordering::Equal {
+                    return Some(candidate.cell);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// How far a [`Solver`] may undo its decisions after a contradiction. The default never backtracks.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Backtracking {
+    /// How many decisions may be undone over the whole run.
+    pub budget: u32,
+    /// How many past decisions are remembered. Older ones can no longer be undone.
+    pub depth: usize,
+}
+
+#[derive(Debug)]
+struct Decision {
+    pub cell: usize,
+    pub tile_index: usize,
+    // how many changes were on the trail before the tile was revealed
+    pub trail_length: usize,
+}
+
+// A restriction placed on a cell before solving, which every fresh board starts with.
+#[derive(Clone, Debug)]
+enum Constraint {
+    Pin { cell: usize, tile_index: usize },
+    Ban { cell: usize, tiles: Bitset },
+}
+
+fn apply_constraint<T: Copy + Ord + Hash>(
+    board: &mut Board,
+    generation: &Generation<T>,
+    constraint: &Constraint,
+) -> Result<(), Contradiction> {
+    match constraint {
+        Constraint::Pin { cell, tile_index } => match board.get(*cell) {
+            Some(Tile::Revealed(revealed)) if revealed == tile_index => Ok(()),
+            Some(Tile::Hidden(possible_tiles)) if possible_tiles.choices.contains(*tile_index) => {
+                reveal(
+                    board,
+                    generation,
+                    *cell,
+                    *tile_index,
+                    &mut (),
+                    &mut Trail::default(),
+                )
+                .map(|_| ())
+            }
+            _ => Err(board.get_contradiction(*cell)),
+        },
+        Constraint::Ban { cell, tiles } => {
+            for tile_index in tiles.iter() {
+                match board.get(*cell) {
+                    Some(Tile::Revealed(revealed)) if *revealed == tile_index => {
+                        return Err(board.get_contradiction(*cell));
+                    }
+                    Some(Tile::Hidden(possible_tiles))
+                        if possible_tiles.choices.contains(tile_index) =>
+                    {
+                        ban(
+                            board,
+                            generation,
+                            *cell,
+                            tile_index,
+                            &mut (),
+                            &mut Trail::default(),
+                        )?;
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn new_board<T: Copy + Ord + Hash>(
+    generation: &Generation<T>,
+    dimensions: Dimensions,
+    wrapping: Wrapping,
+) -> Board {
+    let tile_count = generation.tiles.len();
+
+    Board::new(
+        dimensions,
+        wrapping,
+        generation.neighbourhood,
+        &Tile::Hidden(PossibleTiles::new(
+            Bitset::full(tile_count),
+            &generation.weights,
+        )),
+    )
+}
+
+/// What a call to [`Solver::step`] did to the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// A cell was collapsed to a single tile.
+    Collapsed {
+        /// The index of the cell on the board.
+        cell: usize,
+    },
+    /// The board ran into a contradiction, and earlier decisions were undone to get out of it.
+    Backtracked,
+    /// The board ran into a contradiction it could not get out of, so the next step starts a
+    /// fresh board.
+    Restarted,
+    /// Every cell is revealed.
+    Finished,
+}
+
+/// Generates boards that follow the rules of a [`Generation`], one cell at a time.
+///
+/// A solver starts with a single attempt, no backtracking, no wrapping and a seed of 0. The same
+/// settings always produce the same board. Settings, pinned cells and banned tiles should all be
+/// chosen before the first step.
+#[derive(Debug)]
+pub struct Solver<'generation, T = TileType, O = ()> {
+    generation: &'generation Generation<T>,
+    dimensions: Dimensions,
+    wrapping: Wrapping,
+    backtracking: Backtracking,
+    attempts: u32,
+    observer: O,
+    constraints: Vec<Constraint>,
+    rng: ChaCha8Rng,
+    board: Board,
+    // filled in by the first step of each attempt
+    entropy_queue: Option<EntropyQueue>,
+    decisions: VecDeque<Decision>,
+    trail: Trail,
+    backtracks_left: u32,
+    contradictions: Vec<Contradiction>,
+    finished: bool,
+}
+
+impl<'generation, T: Copy + Ord + Hash> Solver<'generation, T> {
+    /// Creates a solver for boards of the given size.
+    #[must_use]
+    pub fn new(generation: &'generation Generation<T>, dimensions: Dimensions) -> Self {
+        let wrapping = Wrapping::default();
+
+        Self {
+            generation,
+            dimensions,
+            wrapping,
+            backtracking: Backtracking::default(),
+            attempts: 1,
+            observer: (),
+            constraints: vec![],
+            rng: ChaCha8Rng::seed_from_u64(0),
+            board: new_board(generation, dimensions, wrapping),
+            entropy_queue: None,
+            decisions: VecDeque::new(),
+            trail: Trail::default(),
+            backtracks_left: 0,
+            contradictions: vec![],
+            finished: false,
+        }
+    }
+}
+
+impl<'generation, T: Copy + Ord + Hash, O: Observer> Solver<'generation, T, O> {
+    /// Sets which edges of the board wrap around.
+    #[must_use]
+    pub fn with_wrapping(mut self, wrapping: Wrapping) -> Self {
+        self.wrapping = wrapping;
+        // pinned cells that no longer fit together are reported by the first step
+        self.reset_board().ok();
+        self
+    }
+
+    /// Lets the solver undo decisions that led to a contradiction.
+    #[must_use]
+    pub const fn with_backtracking(mut self, backtracking: Backtracking) -> Self {
+        self.backtracking = backtracking;
+        self.backtracks_left = backtracking.budget;
+        self
+    }
+
+    /// Sets how many fresh boards are tried before giving up. At least one always is.
+    #[must_use]
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Sets the seed every random choice is drawn from.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    /// Reports every event of the solve to `observer`. Pass `&mut observer` to keep hold of it.
+    #[must_use]
+    pub fn with_observer<P: Observer>(self, observer: P) -> Solver<'generation, T, P> {
+        Solver {
+            generation: self.generation,
+            dimensions: self.dimensions,
+            wrapping: self.wrapping,
+            backtracking: self.backtracking,
+            attempts: self.attempts,
+            observer,
+            constraints: self.constraints,
+            rng: self.rng,
+            board: self.board,
+            entropy_queue: self.entropy_queue,
+            decisions: self.decisions,
+            trail: self.trail,
+            backtracks_left: self.backtracks_left,
+            contradictions: self.contradictions,
+            finished: self.finished,
+        }
+    }
+
+    /// The rules the board is solved with.
+    #[must_use]
+    pub const fn generation(&self) -> &'generation Generation<T> {
+        self.generation
+    }
+
+    /// The board as it stands. After the last attempt fails, this is the board that failed.
+    #[must_use]
+    pub const fn board(&self) -> &Board {
+        &self.board
+    }
+
+    // Replaces the board with a fresh one that has every constraint applied.
+    fn reset_board(&mut self) -> Result<(), Error> {
+        self.board = new_board(self.generation, self.dimensions, self.wrapping);
+        for constraint in &self.constraints {
+            apply_constraint(&mut self.board, self.generation, constraint)
+                .map_err(|contradiction| Error::ConflictingPins { contradiction })?;
+        }
+
+        Ok(())
+    }
+
+    // Odd rows of a hex board are shifted half a cell, so wrapping an odd number of rows would
+    // bring two shifted rows together and give the cells along the seam mismatched neighbours.
+    fn check_wrapping(&self) -> Result<(), Error> {
+        if self.wrapping.vertical
+            && self.generation.neighbourhood == Neighbourhood::Hexagonal
+            && self.dimensions.height % 2 == 1
+        {
+            return Err(Error::Unsupported {
+                reason: "Hex boards can only wrap vertically with an even number of rows",
+            });
+        }
+
+        Ok(())
+    }
+
+    fn get_constrained_cell(&self, position: (usize, usize, usize)) -> Result<usize, Error> {
+        self.check_wrapping()?;
+        if self.entropy_queue.is_some() || self.finished || !self.contradictions.is_empty() {
+            return Err(Error::Unsupported {
+                reason: "Cells can only be pinned or banned before the first step",
+            });
+        }
+
+        let (width, height, depth) = position;
+        self.board
+            .get_cell(width, height, depth)
+            .ok_or(Error::CoordinateOverflow)
+    }
+
+    fn add_constraint(&mut self, constraint: Constraint) -> Result<(), Error> {
+        let result = apply_constraint(&mut self.board, self.generation, &constraint);
+        self.constraints.push(constraint);
+        if let Err(contradiction) = result {
+            // the constraints before this one fit together, so the board can be rebuilt from them
+            self.constraints.pop();
+            self.reset_board()?;
+            return Err(Error::ConflictingPins { contradiction });
+        }
+
+        Ok(())
+    }
+
+    /// Forces the cell at `(width, height, depth)` to be `tile`, on this board and on every fresh
+    /// one that replaces it. The other cells are narrowed down to fit around it straight away.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the position is outside the board, if the generation has no such tile, if the tile
+    /// cannot be placed next to the cells pinned or banned so far, after the first step, or if a
+    /// hex board wraps an odd number of rows.
+    pub fn pin(&mut self, position: (usize, usize, usize), tile: T) -> Result<(), Error> {
+        let cell = self.get_constrained_cell(position)?;
+        let tile_index = self.generation.get_index(tile).ok_or(Error::UnknownTile)?;
+
+        self.add_constraint(Constraint::Pin { cell, tile_index })
+    }
+
+    /// Rules `tiles` out for the cell at `(width, height, depth)`, on this board and on every fresh
+    /// one that replaces it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the position is outside the board, if the generation is missing any of the tiles,
+    /// if the cell is left without a choice, after the first step, or if a hex board wraps an odd
+    /// number of rows.
+    pub fn ban(&mut self, position: (usize, usize, usize), tiles: &[T]) -> Result<(), Error> {
+        let cell = self.get_constrained_cell(position)?;
+        let mut banned = Bitset::new(self.generation.tiles.len());
+        for tile in tiles {
+            banned.insert(self.generation.get_index(*tile).ok_or(Error::UnknownTile)?);
+        }
+
+        self.add_constraint(Constraint::Ban {
+            cell,
+            tiles: banned,
+        })
+    }
+
+    fn get_failure(&self) -> Error {
+        Error::Contradiction {
+            attempts: self.attempts,
+            contradictions: self.contradictions.clone(),
+        }
+    }
+
+    fn has_attempts_left(&self) -> bool {
+        u32::try_from(self.contradictions.len()).is_ok_and(|failed| failed < self.attempts)
+    }
+
+    // Gives up on the current board. The next step starts over with a fresh one if any attempts
+    // are left.
+    fn restart(&mut self, contradiction: Contradiction) -> Result<Progress, Error> {
+        self.contradictions.push(contradiction);
+        if !self.has_attempts_left() {
+            return Err(self.get_failure());
+        }
+
+        self.entropy_queue = None;
+        self.decisions.clear();
+        self.trail.changes.clear();
+        self.backtracks_left = self.backtracking.budget;
+        Ok(Progress::Restarted)
+    }
+
+    // Remembers a decision so that it can be undone, for as long as backtracking is allowed.
+    fn save_decision(&mut self, cell: usize, tile_index: usize) {
+        self.trail.enabled = self.backtracks_left > 0 && self.backtracking.depth > 0;
+        if !self.trail.enabled {
+            // nothing can be undone any more, so there is no need to keep the history
+            self.decisions.clear();
+            self.trail.changes.clear();
+            return;
+        }
+
+        self.decisions.push_back(Decision {
+            cell,
+            tile_index,
+            trail_length: self.trail.changes.len(),
+        });
+        if self.decisions.len() > self.backtracking.depth {
+            self.decisions.pop_front();
+            // changes made before the oldest remaining decision can no longer be undone
+            let forgotten = self
+                .decisions
+                .front()
+                .map_or(0, |decision| decision.trail_length);
+            self.trail.changes.drain(..forgotten);
+            for decision in &mut self.decisions {
+                decision.trail_length = decision.trail_length.saturating_sub(forgotten);
+            }
+        }
+    }
+
+    /// Collapses the hidden cell with the lowest entropy and propagates the consequences. A
+    /// contradiction is backed out of if backtracking allows it, and otherwise starts a fresh
+    /// board.
+    ///
+    /// # Errors
+    ///
+    /// Returns where each attempt ran into a contradiction once none are left, or
+    /// [`Error::EmptyRuleset`] if the generation has no tiles to place, or [`Error::Unsupported`]
+    /// if a hex board wraps vertically with an odd number of rows. Later steps fail the same way.
+    pub fn step(&mut self) -> Result<Progress, Error> {
+        if self.generation.tiles.is_empty() {
+            return Err(Error::EmptyRuleset);
+        }
+        self.check_wrapping()?;
+        if self.finished {
+            return Ok(Progress::Finished);
+        }
+        if !self.has_attempts_left() {
+            return Err(self.get_failure());
+        }
+
+        // each attempt starts from a fresh board, with the pinned cells in place
+        if self.entropy_queue.is_none() {
+            self.reset_board()?;
+        }
+        // held outside the solver during the step, and put back once the board is consistent
+        let mut entropy_queue = match self.entropy_queue.take() {
+            Some(entropy_queue) => entropy_queue,
+            None => EntropyQueue::from_board(&self.board, &mut self.rng),
+        };
+        let Some(cell) = entropy_queue.pop(&self.board) else {
+            self.finished = true;
+            self.observer.on_completion(&self.board);
+            return Ok(Progress::Finished);
+        };
+
+        let choice = match self.board.get(cell) {
+            Some(Tile::Hidden(possible_tiles)) => {
+                choose_tile(possible_tiles, &self.generation.weights, &mut self.rng)
+            }
+            _ => None,
+        };
+        let mut result = match choice {
+            Some(tile_index) => {
+                self.save_decision(cell, tile_index);
+                reveal(
+                    &mut self.board,
+                    self.generation,
+                    cell,
+                    tile_index,
+                    &mut self.observer,
+                    &mut self.trail,
+                )
+            }
+            None => Err(self.board.get_contradiction(cell)),
+        };
+
+        // undo the most recent decision and rule its choice out until the board is consistent
+        let mut progress = Progress::Collapsed { cell };
+        loop {
+            match result {
+                Ok(changed_cells) => {
+                    for cell in changed_cells {
+                        entropy_queue.push(&self.board, cell, &mut self.rng);
+                    }
+                    break;
+                }
+                Err(contradiction) => {
+                    self.observer.on_contradiction(&self.board, contradiction);
+                    let Some(remaining) = self.backtracks_left.checked_sub(1) else {
+                        return self.restart(contradiction);
+                    };
+                    let Some(decision) = self.decisions.pop_back() else {
+                        return self.restart(contradiction);
+                    };
+                    self.backtracks_left = remaining;
+                    progress = Progress::Backtracked;
+
+                    let restored = self.trail.undo(
+                        &mut self.board,
+                        &self.generation.weights,
+                        decision.trail_length,
+                    );
+                    self.observer.on_backtrack(&self.board, &restored);
+                    entropy_queue = EntropyQueue::from_board(&self.board, &mut self.rng);
+                    result = ban(
+                        &mut self.board,
+                        self.generation,
+                        decision.cell,
+                        decision.tile_index,
+                        &mut self.observer,
+                        &mut self.trail,
+                    );
+                }
+            }
+        }
+
+        self.entropy_queue = Some(entropy_queue);
+        if let Progress::Collapsed { cell } = progress {
+            self.observer.on_collapse(&self.board, cell);
+        }
+        Ok(progress)
+    }
+
+    /// Steps until the board is finished or `duration` has passed, whichever comes first. At least
+    /// one step is always taken, so that a game loop with a tight budget still makes progress.
+    ///
+    /// # Errors
+    ///
+    /// Fails in the same cases as [`Solver::step`].
+    pub fn run_for(&mut self, duration: Duration) -> Result<Progress, Error> {
+        let deadline = Instant::now().checked_add(duration);
+
+        loop {
+            let progress = self.step()?;
+            if progress == Progress::Finished
+                || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Ok(progress);
+            }
+        }
+    }
+
+    /// Steps until every cell is revealed, and hands back the finished board.
+    ///
+    /// # Errors
+    ///
+    /// Returns where each attempt ran into a contradiction if none of them succeeded, or
+    /// [`Error::EmptyRuleset`] if the generation has no tiles to place.
+    pub fn run(mut self) -> Result<Board, Error> {
+        while self.step()? != Progress::Finished {}
+
+        Ok(self.board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direction::Augmentation;
+    use crate::rule::Rule;
+    use crate::sample::generation_init;
+    use std::collections::HashMap;
+
+    // Colours a grid so that neighbouring cells always differ. Three colours on a square grid can
+    // paint themselves into a corner, and on a Moore grid they always do.
+    fn colouring(colours: u8, neighbourhood: Neighbourhood) -> Generation<u8> {
+        let mut ruleset = HashSet::new();
+        for from in 0..colours {
+            for to in 0..colours {
+                if from != to {
+                    for direction in neighbourhood.get_directions() {
+                        ruleset.insert(Rule::new(from, to, direction));
+                    }
+                }
+            }
+        }
+        let frequencies = (0..colours)
+            .map(|colour| (colour, 1.0))
+            .collect::<HashMap<u8, f64>>();
+
+        Generation::new(&ruleset, &frequencies, neighbourhood)
+    }
+
+    const SQUARE: Dimensions = Dimensions {
+        width: 10,
+        height: 10,
+        depth: 1,
+    };
+    // runs into a contradiction on `SQUARE` without backtracking
+    const CONTRADICTING_SEED: u64 = 8;
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    enum Event {
+        Collapse(usize),
+        DomainChange(usize),
+        Contradiction,
+        Backtrack(Vec<usize>),
+        Completion,
+    }
+
+    // Records every callback, and keeps a copy of each cell's choices up to date from them alone.
+    #[derive(Debug, Default)]
+    struct Recorder {
+        events: Vec<Event>,
+        domains: Vec<Option<Bitset>>,
+        tile_count: usize,
+    }
+
+    impl Recorder {
+        fn new(board: &Board, tile_count: usize) -> Self {
+            Self {
+                events: vec![],
+                domains: (0..board.cells().len())
+                    .map(|cell| board.get_domain(tile_count, cell))
+                    .collect(),
+                tile_count,
+            }
+        }
+
+        fn update(&mut self, board: &Board, cell: usize) {
+            if let Some(domain) = self.domains.get_mut(cell) {
+                *domain = board.get_domain(self.tile_count, cell);
+            }
+        }
+
+        fn is_in_sync(&self, board: &Board) -> bool {
+            self.domains
+                .iter()
+                .enumerate()
+                .all(|(cell, domain)| *domain == board.get_domain(self.tile_count, cell))
+        }
+    }
+
+    impl Observer for Recorder {
+        fn on_collapse(&mut self, board: &Board, cell: usize) {
+            self.events.push(Event::Collapse(cell));
+            self.update(board, cell);
+        }
+
+        fn on_domain_change(&mut self, board: &Board, cell: usize) {
+            self.events.push(Event::DomainChange(cell));
+            self.update(board, cell);
+        }
+
+        fn on_contradiction(&mut self, _board: &Board, _contradiction: Contradiction) {
+            self.events.push(Event::Contradiction);
+        }
+
+        fn on_backtrack(&mut self, board: &Board, restored: &[usize]) {
+            self.events.push(Event::Backtrack(restored.to_vec()));
+            for cell in restored {
+                self.update(board, *cell);
+            }
+        }
+
+        fn on_completion(&mut self, _board: &Board) {
+            self.events.push(Event::Completion);
+        }
+    }
+
+    #[test]
+    fn observers_hear_about_every_step_in_order() -> Result<(), Error> {
+        let generation = colouring(3, Neighbourhood::VonNeumann);
+        let board = new_board(&generation, SQUARE, Wrapping::default());
+        let mut recorder = Recorder::new(&board, generation.tiles.len());
+        let mut solver = Solver::new(&generation, SQUARE)
+            .with_seed(CONTRADICTING_SEED)
+            .with_backtracking(Backtracking {
+                budget: 10,
+                depth: usize::MAX,
+            })
+            .with_observer(&mut recorder);
+
+        let mut backtracked = false;
+        loop {
+            let seen = solver.observer.events.len();
+            let progress = solver.step()?;
+            let events = solver.observer.events.get(seen..).unwrap_or_default();
+            // the board never needs a fresh start
+            assert_ne!(progress, Progress::Restarted);
+            match progress {
+                Progress::Collapsed { cell } => {
+                    assert_eq!(events.last(), Some(&Event::Collapse(cell)));
+                    assert!(matches!(solver.board.get(cell), Some(Tile::Revealed(_))));
+                    assert!(events
+                        .iter()
+                        .all(|event| matches!(event, Event::Collapse(_) | Event::DomainChange(_))));
+                }
+                Progress::Backtracked => {
+                    backtracked = true;
+                    // propagating the decision narrows cells before it runs into the contradiction
+                    // that each undo answers
+                    assert!(events
+                        .iter()
+                        .any(|event| matches!(event, Event::Backtrack(_))));
+                    assert!(events.windows(2).all(|pair| match pair {
+                        [previous, Event::Backtrack(_)] => *previous == Event::Contradiction,
+                        _ => true,
+                    }));
+                    assert!(!events
+                        .iter()
+                        .any(|event| matches!(event, Event::Collapse(_) | Event::Completion)));
+                }
+                Progress::Restarted => {}
+                Progress::Finished => {
+                    assert_eq!(events, [Event::Completion]);
+                    break;
+                }
+            }
+            assert!(solver.observer.is_in_sync(&solver.board));
+        }
+
+        assert!(backtracked);
+        // a finished solver stays finished without telling the observer again
+        let seen = solver.observer.events.len();
+        assert_eq!(solver.step()?, Progress::Finished);
+        assert_eq!(solver.observer.events.len(), seen);
+        Ok(())
+    }
+
+    #[test]
+    fn backtracking_hides_the_restored_cells_before_reporting_them() -> Result<(), Error> {
+        #[derive(Default)]
+        struct Check {
+            backtracks: usize,
+            all_hidden: bool,
+        }
+
+        impl Observer for Check {
+            fn on_backtrack(&mut self, board: &Board, restored: &[usize]) {
+                self.backtracks = self.backtracks.saturating_add(1);
+                self.all_hidden = restored
+                    .iter()
+                    .all(|cell| matches!(board.get(*cell), Some(Tile::Hidden(_))));
+            }
+        }
+
+        let generation = colouring(3, Neighbourhood::VonNeumann);
+        let mut check = Check::default();
+        Solver::new(&generation, SQUARE)
+            .with_seed(CONTRADICTING_SEED)
+            .with_backtracking(Backtracking {
+                budget: 10,
+                depth: usize::MAX,
+            })
+            .with_observer(&mut check)
+            .run()?;
+
+        assert_eq!(check.backtracks, 1);
+        assert!(check.all_hidden);
+        Ok(())
+    }
+
+    #[test]
+    fn restarts_when_a_contradiction_cannot_be_backed_out_of() {
+        let generation = colouring(3, Neighbourhood::Moore);
+        let dimensions = Dimensions {
+            width: 4,
+            height: 4,
+            depth: 1,
+        };
+        let mut solver = Solver::new(&generation, dimensions).with_attempts(2);
+
+        let mut restarts = 0_u32;
+        let error = loop {
+            match solver.step() {
+                Ok(Progress::Restarted) => restarts = restarts.saturating_add(1),
+                Ok(_) => {}
+                Err(error) => break error,
+            }
+        };
+
+        assert_eq!(restarts, 1);
+        assert!(matches!(
+            error,
+            Error::Contradiction { attempts: 2, ref contradictions } if contradictions.len() == 2
+        ));
+        // the failure is final
+        assert!(matches!(solver.step(), Err(Error::Contradiction { .. })));
+    }
+
+    #[test]
+    fn run_for_takes_at_least_one_step() -> Result<(), Error> {
+        let generation = colouring(4, Neighbourhood::VonNeumann);
+        let mut solver = Solver::new(&generation, SQUARE);
+
+        assert!(matches!(
+            solver.run_for(Duration::ZERO)?,
+            Progress::Collapsed { .. }
+        ));
+        assert_eq!(solver.run_for(Duration::from_mins(1))?, Progress::Finished);
+        assert!(solver
+            .board()
+            .cells()
+            .iter()
+            .all(|tile| matches!(tile, Tile::Revealed(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn hex_boards_wrap_vertically_only_with_an_even_number_of_rows() -> Result<(), Error> {
+        let generation = generation_init(
+            "resources/beach.bmp",
+            Augmentation::ISOTROPIC,
+            false,
+            Neighbourhood::Hexagonal,
+        )?;
+        let wrapping = Wrapping {
+            horizontal: false,
+            vertical: true,
+        };
+        let solve = |height| {
+            let dimensions = Dimensions {
+                width: 6,
+                height,
+                depth: 1,
+            };
+            Solver::new(&generation, dimensions)
+                .with_wrapping(wrapping)
+                .with_attempts(10)
+                .run()
+        };
+
+        assert!(matches!(solve(5), Err(Error::Unsupported { .. })));
+        assert!(solve(6).is_ok());
+        Ok(())
+    }
+
+    // Every cell is revealed, next to tiles its rules allow.
+    fn is_finished_and_consistent<T: Copy + Ord + Hash>(
+        board: &Board,
+        generation: &Generation<T>,
+    ) -> bool {
+        let get_tile = |cell| match board.get(cell) {
+            Some(Tile::Revealed(tile_index)) => generation.get_tile(*tile_index),
+            _ => None,
+        };
+
+        (0..board.cells().len()).all(|cell| {
+            get_tile(cell).is_some_and(|tile| {
+                generation.neighbourhood.get_directions().all(|direction| {
+                    board
+                        .get_neighbour(cell, direction)
+                        .is_none_or(|neighbour| {
+                            get_tile(neighbour).is_some_and(|neighbour_tile| {
+                                generation
+                                    .get_compatible(tile, direction)
+                                    .contains(&neighbour_tile)
+                            })
+                        })
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn backtracking_gets_out_of_contradictions() -> Result<(), Error> {
+        let generation = colouring(3, Neighbourhood::VonNeumann);
+        let solve = |seed, budget| {
+            Solver::new(&generation, SQUARE)
+                .with_seed(seed)
+                .with_backtracking(Backtracking {
+                    budget,
+                    depth: usize::MAX,
+                })
+                .run()
+        };
+
+        assert!(matches!(
+            solve(CONTRADICTING_SEED, 0),
+            Err(Error::Contradiction { .. })
+        ));
+        assert!(is_finished_and_consistent(
+            &solve(CONTRADICTING_SEED, 10)?,
+            &generation
+        ));
+        for seed in 0..20 {
+            assert!(is_finished_and_consistent(
+                &solve(seed, 10_000)?,
+                &generation
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn backtracking_depth_limits_which_decisions_are_undone() {
+        // Tracks the cells collapsed by earlier steps, and whether backtracking hid any of them.
+        #[derive(Default)]
+        struct History {
+            collapsed: HashSet<usize>,
+            backtracks: usize,
+            earlier_steps_undone: bool,
+        }
+
+        impl Observer for History {
+            fn on_collapse(&mut self, _board: &Board, cell: usize) {
+                self.collapsed.insert(cell);
+            }
+
+            fn on_backtrack(&mut self, _board: &Board, restored: &[usize]) {
+                self.backtracks = self.backtracks.saturating_add(1);
+                for cell in restored {
+                    self.earlier_steps_undone |= self.collapsed.remove(cell);
+                }
+            }
+        }
+
+        let generation = colouring(3, Neighbourhood::VonNeumann);
+        // this seed needs more than one undo in a row to get out of a contradiction
+        let solve = |depth| {
+            let mut history = History::default();
+            let mut solver = Solver::new(&generation, SQUARE)
+                .with_seed(6)
+                .with_backtracking(Backtracking { budget: 100, depth })
+                .with_observer(&mut history);
+            let mut remembered = 0;
+            while matches!(solver.step(), Ok(progress) if progress != Progress::Finished) {
+                remembered = remembered.max(solver.decisions.len());
+            }
+            (history.backtracks, history.earlier_steps_undone, remembered)
+        };
+
+        // only the decision that ran into the contradiction is taken back
+        let (backtracks, earlier_steps_undone, remembered) = solve(1);
+        assert!(backtracks > 0);
+        assert!(!earlier_steps_undone);
+        assert_eq!(remembered, 1);
+
+        let (backtracks, earlier_steps_undone, remembered) = solve(2);
+        assert!(backtracks > 0);
+        assert!(earlier_steps_undone);
+        assert_eq!(remembered, 2);
+    }
+
+    #[test]
+    fn pinned_cells_keep_their_tile() -> Result<(), Error> {
+        let generation = colouring(3, Neighbourhood::VonNeumann);
+        let mut solver = Solver::new(&generation, SQUARE)
+            .with_backtracking(Backtracking {
+                budget: 100,
+                depth: usize::MAX,
+            })
+            .with_attempts(10);
+        solver.pin((2, 3, 0), 1)?;
+        solver.pin((9, 9, 0), 2)?;
+        let board = solver.run()?;
+
+        assert!(is_finished_and_consistent(&board, &generation));
+        assert!(matches!(
+            board.get_cell(2, 3, 0).and_then(|cell| board.get(cell)),
+            Some(Tile::Revealed(tile_index)) if generation.get_tile(*tile_index) == Some(1)
+        ));
+        assert!(matches!(
+            board.get_cell(9, 9, 0).and_then(|cell| board.get(cell)),
+            Some(Tile::Revealed(tile_index)) if generation.get_tile(*tile_index) == Some(2)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn banned_tiles_never_appear() -> Result<(), Error> {
+        let generation = colouring(3, Neighbourhood::VonNeumann);
+        let mut solver = Solver::new(&generation, SQUARE)
+            .with_backtracking(Backtracking {
+                budget: 100,
+                depth: usize::MAX,
+            })
+            .with_attempts(10);
+        for width in 0..SQUARE.width {
+            solver.ban((width, 0, 0), &[0])?;
+        }
+        let board = solver.run()?;
+
+        assert!(is_finished_and_consistent(&board, &generation));
+        assert!((0..SQUARE.width).all(|width| matches!(
+            board.get_cell(width, 0, 0).and_then(|cell| board.get(cell)),
+            Some(Tile::Revealed(tile_index)) if generation.get_tile(*tile_index) != Some(0)
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn conflicting_pins_leave_the_earlier_ones_in_place() -> Result<(), Error> {
+        let generation = colouring(3, Neighbourhood::VonNeumann);
+        let mut solver = Solver::new(&generation, SQUARE);
+        solver.pin((0, 0, 0), 0)?;
+
+        assert!(matches!(
+            solver.pin((1, 0, 0), 0),
+            Err(Error::ConflictingPins { .. })
+        ));
+        assert!(matches!(
+            solver.ban((0, 0, 0), &[0]),
+            Err(Error::ConflictingPins { .. })
+        ));
+        let zero = generation.get_index(0).ok_or(Error::UnknownTile)?;
+        assert!(
+            matches!(solver.board().get(0), Some(Tile::Revealed(tile_index)) if *tile_index == zero)
+        );
+        assert!(matches!(
+            solver.board().get(1),
+            Some(Tile::Hidden(possible_tiles)) if !possible_tiles.choices.contains(zero)
+        ));
+
+        // the rejected pin is forgotten, so its neighbour can still be pinned to something else
+        solver.pin((1, 0, 0), 1)?;
+        Ok(())
+    }
+
+    #[test]
+    fn cells_can_only_be_constrained_before_the_first_step() -> Result<(), Error> {
+        let generation = colouring(3, Neighbourhood::VonNeumann);
+        let mut solver = Solver::new(&generation, SQUARE);
+        solver.step()?;
+
+        assert!(matches!(
+            solver.pin((0, 0, 0), 0),
+            Err(Error::Unsupported { .. })
+        ));
+        assert!(matches!(
+            solver.ban((0, 0, 0), &[0]),
+            Err(Error::Unsupported { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn pins_are_reapplied_after_a_restart() -> Result<(), Error> {
+        // every attempt runs into a contradiction, since the cells around a corner all touch
+        let generation = colouring(3, Neighbourhood::Moore);
+        let dimensions = Dimensions {
+            width: 4,
+            height: 4,
+            depth: 1,
+        };
+        let mut solver = Solver::new(&generation, dimensions).with_attempts(3);
+        solver.pin((3, 3, 0), 1)?;
+        let pinned = solver
+            .board()
+            .get_cell(3, 3, 0)
+            .ok_or(Error::CoordinateOverflow)?;
+        let neighbour = solver
+            .board()
+            .get_cell(2, 2, 0)
+            .ok_or(Error::CoordinateOverflow)?;
+        let one = generation.get_index(1).ok_or(Error::UnknownTile)?;
+
+        while solver.step()? != Progress::Restarted {}
+        solver.step()?;
+
+        // the fresh board has the pinned tile, and its neighbours were narrowed around it
+        assert!(matches!(
+            solver.board().get(pinned),
+            Some(Tile::Revealed(tile_index)) if *tile_index == one
+        ));
+        assert!(solver
+            .board()
+            .get_domain(generation.tiles.len(), neighbour)
+            .is_some_and(|domain| !domain.contains(one)));
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use crate::error::Error;
+use core::hash::BuildHasher;
+use imgproc_rs::image::BaseImage;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// A tile of the simple model, identified by its colour in the sample image.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TileType {
+    /// Red, green and blue channels.
+    pub rgb: [u8; 3],
+}
+
+/// Drawn for cells that have no colour of their own.
+pub const INVALID_TILE: TileType = TileType { rgb: [255, 0, 0] };
+/// Water in the beach samples.
+pub const WATER_TILE: TileType = TileType { rgb: [63, 72, 204] };
+/// Sand in the beach samples.
+pub const COAST_TILE: TileType = TileType {
+    rgb: [255, 201, 14],
+};
+/// Grass in the beach samples.
+pub const GRASS_TILE: TileType = TileType { rgb: [34, 177, 76] };
+
+impl TileType {
+    /// Reads the pixel at the given position, ignoring any alpha channel.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the image has fewer than three channels, such as a greyscale image.
+    pub fn from_pixel(image: &dyn BaseImage<u8>, width: u32, height: u32) -> Result<Self, Error> {
+        let raw_pixel = image.get_pixel(width, height);
+        let [first, second, third, ..] = raw_pixel else {
+            return Err(Error::UnsupportedChannelCount {
+                channels: raw_pixel.len(),
+            });
+        };
+
+        Ok(Self {
+            rgb: [*first, *second, *third],
+        })
+    }
+}
+
+// Parses a tile from its red, green and blue values, e.g. "63,72,204".
+impl FromStr for TileType {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let channels = value
+            .split(',')
+            .map(|channel| channel.trim().parse::<u8>().map_err(|_| ()))
+            .collect::<Result<Vec<u8>, ()>>()?;
+        let [red, green, blue] = channels.as_slice() else {
+            return Err(());
+        };
+
+        Ok(Self {
+            rgb: [*red, *green, *blue],
+        })
+    }
+}
+
+impl<'src, S> FromIterator<&'src TileType> for HashSet<TileType, S>
+where
+    S: BuildHasher + Default,
+{
+    fn from_iter<T: IntoIterator<Item = &'src TileType>>(iter: T) -> Self {
+        iter.into_iter().copied().collect()
+    }
+}
+
+/// The average colour of `tiles`, channel by channel, or `None` if there are none.
+pub fn get_average_colour(tiles: impl IntoIterator<Item = TileType>) -> Option<TileType> {
+    let mut sums = [0_u64; 3];
+    let mut count = 0_u64;
+    for tile in tiles {
+        for (sum, channel) in sums.iter_mut().zip(tile.rgb) {
+            *sum = sum.checked_add(u64::from(channel))?;
+        }
+        count = count.checked_add(1)?;
+    }
+
+    let [red, green, blue] = sums.map(|sum| {
+        sum.checked_div(count)
+            .and_then(|average| u8::try_from(average).ok())
+    });
+    Some(TileType {
+        rgb: [red?, green?, blue?],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_each_channel() {
+        let tiles = [
+            TileType { rgb: [0, 10, 255] },
+            TileType { rgb: [3, 20, 255] },
+        ];
+        assert_eq!(
+            get_average_colour(tiles),
+            Some(TileType { rgb: [1, 15, 255] })
+        );
+        assert_eq!(get_average_colour([]), None);
+    }
+}
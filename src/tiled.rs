@@ -0,0 +1,414 @@
+use crate::direction::{Direction, Neighbourhood};
+use crate::error::Error;
+use crate::generation::Generation;
+use crate::rule::Rule;
+use crate::tile::{get_average_colour, TileType, INVALID_TILE};
+use imgproc_rs::image::BaseImage;
+use imgproc_rs::io;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::iter;
+use std::path::Path;
+use std::str::FromStr;
+use strum_macros::EnumString;
+
+/// The shapes a tile's image can have, named after letters with the same symmetry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumString)]
+pub enum Symmetry {
+    /// Looks the same at every rotation.
+    X,
+    /// Looks the same after a half turn, like a straight path.
+    I,
+    /// Has four distinct rotations, like a corner.
+    L,
+    /// Has four distinct rotations, like a junction.
+    T,
+    /// Looks the same after a half turn, like a diagonal line.
+    #[strum(serialize = "\\")]
+    Diagonal,
+}
+
+impl Symmetry {
+    /// how many distinct tiles a quarter-turn rotation produces
+    #[must_use]
+    pub const fn get_orientations(self) -> u8 {
+        match self {
+            Self::X => 1,
+            Self::I | Self::Diagonal => 2,
+            Self::L | Self::T => 4,
+        }
+    }
+}
+
+/// A `tile` entry of a tileset.
+#[derive(Clone, Debug)]
+pub struct TileDefinition {
+    /// The name neighbour entries refer to the tile by.
+    pub name: String,
+    /// The average colour of the tile's image.
+    pub colour: TileType,
+    /// How likely each orientation of the tile is to be chosen.
+    pub weight: f64,
+    /// Which rotations of the tile are distinct.
+    pub symmetry: Symmetry,
+}
+
+/// One orientation of a tile in a tileset.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TiledTile {
+    /// Position in `TiledModel::tiles`.
+    pub index: usize,
+    /// Number of quarter turns clockwise.
+    pub rotation: u8,
+}
+
+/// The tiles of a tileset and the rules for which of them fit together.
+#[derive(Debug)]
+pub struct TiledModel {
+    /// Every tile, in the order they were defined.
+    pub tiles: Vec<TileDefinition>,
+    /// The rules to solve boards of tiles with.
+    pub generation: Generation<TiledTile>,
+}
+
+impl TiledModel {
+    /// Each cell is drawn with the average colour of its tile's image.
+    #[must_use]
+    pub fn get_colour(&self, tile: TiledTile) -> TileType {
+        self.tiles
+            .get(tile.index)
+            .map_or(INVALID_TILE, |definition| definition.colour)
+    }
+
+    fn rotate(&self, tile: TiledTile, quarter_turns: u8) -> Option<TiledTile> {
+        let orientations = self.tiles.get(tile.index)?.symmetry.get_orientations();
+
+        Some(TiledTile {
+            index: tile.index,
+            rotation: tile
+                .rotation
+                .checked_add(quarter_turns)?
+                .checked_rem(orientations)?,
+        })
+    }
+
+    fn find_tile(&self, reference: &str) -> Option<TiledTile> {
+        let (name, rotation) = match reference.split_once(':') {
+            Some((name, rotation)) => (name, rotation.parse().ok()?),
+            None => (reference, 0),
+        };
+        let index = self.tiles.iter().position(|tile| tile.name == name)?;
+
+        self.rotate(TiledTile { index, rotation: 0 }, rotation)
+    }
+}
+
+// The colour a tile is drawn with, the average of its image.
+fn get_image_colour(image: &dyn BaseImage<u8>) -> Result<TileType, Error> {
+    let (max_width, max_height) = image.info().wh();
+    let mut pixels = vec![];
+    for height in 0..max_height {
+        for width in 0..max_width {
+            pixels.push(TileType::from_pixel(image, width, height)?);
+        }
+    }
+
+    // an empty image averages to black
+    Ok(get_average_colour(pixels).unwrap_or(TileType { rgb: [0; 3] }))
+}
+
+// Reads a `tile <name> <image> <weight> <symmetry>` entry, loading its image to find its colour.
+fn parse_tile(
+    directory: &Path,
+    line_number: usize,
+    [name, image_path, weight, symmetry]: [&str; 4],
+) -> Result<TileDefinition, Error> {
+    let image_path = directory.join(image_path);
+    let Some(image_path) = image_path.to_str() else {
+        return Err(Error::InvalidTileset {
+            line: line_number,
+            reason: "the image path is not valid UTF-8",
+        });
+    };
+    let image = io::read(image_path).map_err(|error| Error::from_image(image_path, error))?;
+    let colour = get_image_colour(&image)?;
+    let (Some(weight), Ok(symmetry)) = (
+        weight
+            .parse::<f64>()
+            .ok()
+            .filter(|weight| weight.is_finite() && *weight >= 0.0),
+        Symmetry::from_str(symmetry),
+    ) else {
+        return Err(Error::InvalidTileset {
+            line: line_number,
+            reason: "invalid weight or symmetry",
+        });
+    };
+
+    Ok(TileDefinition {
+        name: name.to_owned(),
+        colour,
+        weight,
+        symmetry,
+    })
+}
+
+/// Reads a tileset definition.
+///
+/// The file has one entry per line:
+///
+/// ```text
+/// tile <name> <image> <weight> <symmetry>
+/// neighbour <name>[:<rotation>] <direction> <name>[:<rotation>]
+/// ```
+///
+/// Image paths are relative to the tileset file. Every neighbour entry is also applied to its
+/// rotations, so only one orientation of each adjacency needs to be written down.
+///
+/// # Errors
+///
+/// Fails if the tileset or one of its images cannot be read, if a line is malformed, refers to an
+/// unknown tile or to a direction outside the neighbourhood, if no neighbours are given, and for
+/// hex and voxel grids.
+pub fn tileset_init(tileset_path: &str, neighbourhood: Neighbourhood) -> Result<TiledModel, Error> {
+    match neighbourhood {
+        // tile rotations are quarter turns, which do not map hex directions onto each other
+        Neighbourhood::Hexagonal => {
+            return Err(Error::Unsupported {
+                reason: "Tilesets do not support hex grids",
+            })
+        }
+        // tiles are flat images, so they have nothing to show above, below, in front or behind
+        Neighbourhood::Voxel => {
+            return Err(Error::Unsupported {
+                reason: "Tilesets do not support voxel grids",
+            })
+        }
+        Neighbourhood::VonNeumann | Neighbourhood::Moore => {}
+    }
+
+    let contents = fs::read_to_string(tileset_path).map_err(|error| Error::Io {
+        path: tileset_path.to_owned(),
+        message: error.to_string(),
+    })?;
+    let directory = Path::new(tileset_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+
+    let mut model = TiledModel {
+        tiles: vec![],
+        generation: Generation::new(
+            &HashSet::new(),
+            &HashMap::<TiledTile, f64>::new(),
+            neighbourhood,
+        ),
+    };
+    let mut neighbours = vec![];
+
+    for (line_index, line) in contents.lines().enumerate() {
+        let line_number = line_index.saturating_add(1);
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+
+        match words.as_slice() {
+            [] => {}
+            [first, ..] if first.starts_with('#') => {}
+            ["tile", name, image_path, weight, symmetry] => {
+                let entry = [*name, *image_path, *weight, *symmetry];
+                model.tiles.push(parse_tile(directory, line_number, entry)?);
+            }
+            ["neighbour", from, direction, to] => {
+                neighbours.push((line_number, *from, *direction, *to));
+            }
+            _ => {
+                return Err(Error::InvalidTileset {
+                    line: line_number,
+                    reason: "expected a tile or neighbour entry",
+                });
+            }
+        }
+    }
+
+    let mut ruleset = HashSet::<Rule<TiledTile>>::new();
+    let mut frequencies = HashMap::<TiledTile, f64>::new();
+    for (index, tile) in model.tiles.iter().enumerate() {
+        for rotation in 0..tile.symmetry.get_orientations() {
+            frequencies.insert(TiledTile { index, rotation }, tile.weight);
+        }
+    }
+
+    for (line_number, from, direction, to) in neighbours {
+        let (Some(from), Ok(direction), Some(to)) = (
+            model.find_tile(from),
+            Direction::from_str(direction),
+            model.find_tile(to),
+        ) else {
+            return Err(Error::InvalidTileset {
+                line: line_number,
+                reason: "unknown tile or direction",
+            });
+        };
+        // a rule in any other direction would never be checked
+        if !neighbourhood
+            .get_directions()
+            .any(|allowed| allowed == direction)
+        {
+            return Err(Error::InvalidTileset {
+                line: line_number,
+                reason: "the direction is not part of the neighbourhood",
+            });
+        }
+
+        // each quarter turn of the tiles goes with a quarter turn of the direction between them
+        let directions = iter::once(direction).chain(neighbourhood.get_rotations(direction));
+        for (quarter_turns, rotated_direction) in (0..4).zip(directions) {
+            let (Some(rotated_from), Some(rotated_to)) = (
+                model.rotate(from, quarter_turns),
+                model.rotate(to, quarter_turns),
+            ) else {
+                return Err(Error::CoordinateOverflow);
+            };
+
+            ruleset.insert(Rule::new(rotated_from, rotated_to, rotated_direction));
+            ruleset.insert(Rule::reverse(rotated_from, rotated_to, rotated_direction));
+        }
+    }
+    if ruleset.is_empty() {
+        return Err(Error::EmptyRuleset);
+    }
+    model.generation = Generation::new(&ruleset, &frequencies, neighbourhood);
+
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    // writes a tileset to the temporary directory, with images from the bundled beach tileset
+    fn write_tileset(name: &str, contents: &str) -> Result<PathBuf, Error> {
+        let tiles = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/tiles");
+        let path = env::temp_dir().join(format!("{name}-{}.tileset", std::process::id()));
+        let contents = contents.replace("<tiles>", &tiles.to_string_lossy());
+        fs::write(&path, contents).map_err(|error| Error::Io {
+            path: path.to_string_lossy().into_owned(),
+            message: error.to_string(),
+        })?;
+        Ok(path)
+    }
+
+    fn read_tileset(name: &str, contents: &str) -> Result<TiledModel, Error> {
+        let path = write_tileset(name, contents)?;
+        let model = tileset_init(&path.to_string_lossy(), Neighbourhood::VonNeumann);
+        fs::remove_file(path).ok();
+        model
+    }
+
+    #[test]
+    fn reads_the_beach_tileset() -> Result<(), Error> {
+        let model = tileset_init("resources/beach.tileset", Neighbourhood::VonNeumann)?;
+        let names = model.tiles.iter().map(|tile| tile.name.as_str());
+        assert!(names.eq(["water", "sand", "grass", "shore"]));
+        assert_eq!(
+            model
+                .tiles
+                .iter()
+                .map(|tile| tile.symmetry)
+                .collect::<Vec<_>>(),
+            [Symmetry::X, Symmetry::X, Symmetry::X, Symmetry::T]
+        );
+        // one orientation of each symmetric tile and four of the shore
+        assert_eq!(model.generation.tiles.len(), 7);
+        let weight_sum = model.generation.weights().iter().sum::<f64>();
+        assert!((weight_sum - 13.0).abs() < f64::EPSILON);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_hex_and_voxel_grids() {
+        for neighbourhood in [Neighbourhood::Hexagonal, Neighbourhood::Voxel] {
+            assert!(matches!(
+                tileset_init("resources/beach.tileset", neighbourhood),
+                Err(Error::Unsupported { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn finds_tiles_by_name_and_rotation() -> Result<(), Error> {
+        let model = tileset_init("resources/beach.tileset", Neighbourhood::VonNeumann)?;
+        let shore = |rotation| TiledTile { index: 3, rotation };
+        assert_eq!(model.find_tile("shore"), Some(shore(0)));
+        assert_eq!(model.find_tile("shore:1"), Some(shore(1)));
+        assert_eq!(model.find_tile("shore:5"), Some(shore(1)));
+        assert_eq!(
+            model.find_tile("water:3"),
+            Some(TiledTile {
+                index: 0,
+                rotation: 0
+            })
+        );
+        assert_eq!(model.find_tile("lava"), None);
+        assert_eq!(model.find_tile("shore:x"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn rotates_neighbour_entries() -> Result<(), Error> {
+        let model = tileset_init("resources/beach.tileset", Neighbourhood::VonNeumann)?;
+        let water = TiledTile {
+            index: 0,
+            rotation: 0,
+        };
+        let shore = |rotation| TiledTile { index: 3, rotation };
+
+        // `neighbour water down shore` also holds a quarter turn later, and read backwards
+        let below = model.generation.get_compatible(water, Direction::Down);
+        assert!(below.contains(&shore(0)));
+        assert!(!below.contains(&shore(1)));
+        let left = model.generation.get_compatible(water, Direction::Left);
+        assert!(left.contains(&shore(1)));
+        let above = model.generation.get_compatible(shore(0), Direction::Up);
+        assert!(above.contains(&water));
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_line_of_malformed_entries() {
+        let tiles = "tile water <tiles>/water.bmp 1 X\n";
+        let invalid_line = |result: Result<TiledModel, Error>| match result {
+            Err(Error::InvalidTileset { line, .. }) => Some(line),
+            _ => None,
+        };
+
+        let missing_symmetry = read_tileset("missing-symmetry", "# comment\n\ntile water a.bmp 1");
+        assert_eq!(invalid_line(missing_symmetry), Some(3));
+        let bad_weight = tiles.replace(" 1 X", " -1 X");
+        assert_eq!(
+            invalid_line(read_tileset("bad-weight", &bad_weight)),
+            Some(1)
+        );
+        let bad_symmetry = tiles.replace(" 1 X", " 1 Q");
+        assert_eq!(
+            invalid_line(read_tileset("bad-symmetry", &bad_symmetry)),
+            Some(1)
+        );
+        let unknown_tile = format!("{tiles}neighbour water right lava\n");
+        assert_eq!(
+            invalid_line(read_tileset("unknown-tile", &unknown_tile)),
+            Some(2)
+        );
+        let diagonal = format!("{tiles}neighbour water up_left water\n");
+        assert_eq!(invalid_line(read_tileset("diagonal", &diagonal)), Some(2));
+        let unknown_direction = format!("{tiles}neighbour water sideways water\n");
+        assert_eq!(
+            invalid_line(read_tileset("unknown-direction", &unknown_direction)),
+            Some(2)
+        );
+        assert!(matches!(
+            read_tileset("no-neighbours", tiles),
+            Err(Error::EmptyRuleset)
+        ));
+    }
+}
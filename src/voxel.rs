@@ -0,0 +1,450 @@
+use crate::board::{Board, Tile};
+use crate::direction::{Augmentation, Neighbourhood};
+use crate::error::Error;
+use crate::generation::Generation;
+use crate::rule::Rule;
+use crate::sample::{insert_symmetric_rules, offset_in_sample};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+/// A voxel's colour, as an index into the palette of a `MagicaVoxel` file. Index 0 is empty space.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Voxel {
+    /// Position in the palette, counting from one.
+    pub colour_index: u8,
+}
+
+/// Empty space.
+pub const EMPTY_VOXEL: Voxel = Voxel { colour_index: 0 };
+
+/// A dense block of voxels, laid out like a `Board`: rows run from the top down and layers from the
+/// front back. `MagicaVoxel`'s z axis points up and its y axis points back.
+#[derive(Clone, Debug)]
+pub struct VoxelGrid {
+    /// Number of columns.
+    pub width: u32,
+    /// Number of rows.
+    pub height: u32,
+    /// Number of layers.
+    pub depth: u32,
+    /// Every voxel, layer by layer and row by row.
+    pub voxels: Vec<Voxel>,
+    /// the RGBA colours of indices 1 to 255, followed by an unused entry, if the file had a palette
+    pub palette: Option<Vec<[u8; 4]>>,
+}
+
+impl VoxelGrid {
+    fn get_index(&self, width: u32, height: u32, depth: u32) -> Option<usize> {
+        if width >= self.width || height >= self.height || depth >= self.depth {
+            return None;
+        }
+
+        let index = depth
+            .checked_mul(self.height)?
+            .checked_add(height)?
+            .checked_mul(self.width)?
+            .checked_add(width)?;
+        usize::try_from(index).ok()
+    }
+
+    /// The voxel at the given position.
+    #[must_use]
+    pub fn get(&self, width: u32, height: u32, depth: u32) -> Option<Voxel> {
+        self.voxels
+            .get(self.get_index(width, height, depth)?)
+            .copied()
+    }
+}
+
+const VOX_VERSION: u32 = 150;
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let end = offset.checked_add(4)?;
+    Some(u32::from_le_bytes(bytes.get(offset..end)?.try_into().ok()?))
+}
+
+// The size, voxel positions and palette chunks of the file, whichever are present.
+type Chunks<'bytes> = (
+    Option<(u32, u32, u32)>,
+    Option<&'bytes [u8]>,
+    Option<Vec<[u8; 4]>>,
+);
+
+fn read_chunks(bytes: &[u8]) -> Option<Chunks<'_>> {
+    let mut size = None;
+    let mut positions: Option<&[u8]> = None;
+    let mut palette = None;
+
+    // the chunks nested in MAIN follow its 12 byte header
+    let mut offset = 20_usize;
+    while offset < bytes.len() {
+        let content_start = offset.checked_add(12)?;
+        let content_end = content_start
+            .checked_add(usize::try_from(read_u32(bytes, offset.checked_add(4)?)?).ok()?)?;
+        let children_size = usize::try_from(read_u32(bytes, offset.checked_add(8)?)?).ok()?;
+        let content = bytes.get(content_start..content_end)?;
+
+        match bytes.get(offset..content_start.checked_sub(8)?)? {
+            b"SIZE" if size.is_none() => {
+                size = Some((
+                    read_u32(content, 0)?,
+                    read_u32(content, 4)?,
+                    read_u32(content, 8)?,
+                ));
+            }
+            b"XYZI" if positions.is_none() => positions = Some(content.get(4..)?),
+            b"RGBA" => {
+                palette = Some(
+                    content
+                        .chunks_exact(4)
+                        .filter_map(|colour| colour.try_into().ok())
+                        .collect::<Vec<[u8; 4]>>(),
+                );
+            }
+            _ => {}
+        }
+
+        offset = content_end.checked_add(children_size)?;
+    }
+
+    Some((size, positions, palette))
+}
+
+/// Reads the first model of a `MagicaVoxel` file. Scene graph, material and layer chunks are skipped.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, is not a `MagicaVoxel` file, or does not contain a model.
+pub fn read_vox(path: &str) -> Result<VoxelGrid, Error> {
+    let bytes = fs::read(path).map_err(|error| Error::Io {
+        path: path.to_owned(),
+        message: error.to_string(),
+    })?;
+    if bytes.get(0..4) != Some(b"VOX ".as_slice()) || bytes.get(8..12) != Some(b"MAIN".as_slice()) {
+        return Err(Error::InvalidVox {
+            reason: "missing the VOX header",
+        });
+    }
+
+    let Some((size, positions, palette)) = read_chunks(&bytes) else {
+        return Err(Error::InvalidVox {
+            reason: "a chunk runs past the end of the file",
+        });
+    };
+    let (Some((size_x, size_y, size_z)), Some(positions)) = (size, positions) else {
+        return Err(Error::InvalidVox {
+            reason: "the file does not contain a model",
+        });
+    };
+
+    let voxel_count = size_x
+        .checked_mul(size_y)
+        .and_then(|area| area.checked_mul(size_z))
+        .and_then(|volume| usize::try_from(volume).ok())
+        .ok_or(Error::CoordinateOverflow)?;
+    let mut grid = VoxelGrid {
+        width: size_x,
+        height: size_z,
+        depth: size_y,
+        voxels: vec![EMPTY_VOXEL; voxel_count],
+        palette,
+    };
+    for position in positions.chunks_exact(4) {
+        let [x, y, z, colour_index] = <[u8; 4]>::try_from(position).unwrap_or_default();
+        // voxels outside the model's bounds are skipped, as MagicaVoxel does
+        let Some(height) = size_z
+            .checked_sub(u32::from(z))
+            .and_then(|height| height.checked_sub(1))
+        else {
+            continue;
+        };
+        let Some(index) = grid.get_index(u32::from(x), height, u32::from(y)) else {
+            continue;
+        };
+        if let Some(voxel) = grid.voxels.get_mut(index) {
+            *voxel = Voxel { colour_index };
+        }
+    }
+
+    Ok(grid)
+}
+
+fn push_chunk(bytes: &mut Vec<u8>, id: [u8; 4], content: &[u8]) -> Option<()> {
+    bytes.extend_from_slice(&id);
+    bytes.extend_from_slice(&u32::try_from(content.len()).ok()?.to_le_bytes());
+    bytes.extend_from_slice(&0_u32.to_le_bytes());
+    bytes.extend_from_slice(content);
+    Some(())
+}
+
+/// Writes a grid to a `MagicaVoxel` file, with the palette it was read with.
+///
+/// # Errors
+///
+/// `MagicaVoxel` stores coordinates as single bytes, so grids larger than 256 voxels along any axis
+/// are rejected, as are failures to write the file.
+pub fn write_vox(grid: &VoxelGrid, path: &str) -> Result<(), Error> {
+    let too_large = || Error::CoordinateOverflow;
+
+    let mut positions = vec![];
+    for depth in 0..grid.depth {
+        for height in 0..grid.height {
+            for width in 0..grid.width {
+                let voxel = grid.get(width, height, depth).unwrap_or(EMPTY_VOXEL);
+                if voxel == EMPTY_VOXEL {
+                    continue;
+                }
+
+                let z = grid
+                    .height
+                    .checked_sub(height)
+                    .and_then(|z| z.checked_sub(1))
+                    .ok_or_else(too_large)?;
+                for coordinate in [width, depth, z] {
+                    positions.push(u8::try_from(coordinate).map_err(|_| too_large())?);
+                }
+                positions.push(voxel.colour_index);
+            }
+        }
+    }
+
+    let mut size = vec![];
+    for length in [grid.width, grid.depth, grid.height] {
+        if length > 256 {
+            return Err(too_large());
+        }
+        size.extend_from_slice(&length.to_le_bytes());
+    }
+
+    let mut voxels = u32::try_from(positions.len() / 4)
+        .map_err(|_| too_large())?
+        .to_le_bytes()
+        .to_vec();
+    voxels.extend_from_slice(&positions);
+
+    let mut children = vec![];
+    push_chunk(&mut children, *b"SIZE", &size).ok_or_else(too_large)?;
+    push_chunk(&mut children, *b"XYZI", &voxels).ok_or_else(too_large)?;
+    if let Some(palette) = &grid.palette {
+        push_chunk(&mut children, *b"RGBA", &palette.concat()).ok_or_else(too_large)?;
+    }
+
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend_from_slice(&VOX_VERSION.to_le_bytes());
+    bytes.extend_from_slice(b"MAIN");
+    bytes.extend_from_slice(&0_u32.to_le_bytes());
+    bytes.extend_from_slice(
+        &u32::try_from(children.len())
+            .map_err(|_| too_large())?
+            .to_le_bytes(),
+    );
+    bytes.extend_from_slice(&children);
+
+    fs::write(path, bytes).map_err(|error| Error::Io {
+        path: path.to_owned(),
+        message: error.to_string(),
+    })
+}
+
+/// The rules learned from a voxel sample, along with the palette to save results with.
+#[derive(Debug)]
+pub struct VoxelModel {
+    /// The palette of the sample, if it had one.
+    pub palette: Option<Vec<[u8; 4]>>,
+    /// The rules to solve boards of voxels with.
+    pub generation: Generation<Voxel>,
+}
+
+impl VoxelModel {
+    /// Converts a solved board into voxels. Hidden cells are left empty.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the board is too large to measure in `u32`.
+    pub fn to_grid(&self, board: &Board) -> Result<VoxelGrid, Error> {
+        let voxels = board
+            .cells()
+            .iter()
+            .map(|tile| match tile {
+                Tile::Revealed(index) => self.generation.get_tile(*index).unwrap_or(EMPTY_VOXEL),
+                Tile::Hidden(_) => EMPTY_VOXEL,
+            })
+            .collect();
+
+        let to_length =
+            |length: usize| u32::try_from(length).map_err(|_| Error::CoordinateOverflow);
+        Ok(VoxelGrid {
+            width: to_length(board.dimensions().width)?,
+            height: to_length(board.dimensions().height)?,
+            depth: to_length(board.dimensions().depth)?,
+            voxels,
+            palette: self.palette.clone(),
+        })
+    }
+}
+
+/// Learns which voxels, including empty space, sit next to each other across all six faces.
+///
+/// # Errors
+///
+/// Fails if the file cannot be read as a `MagicaVoxel` model, or the model has a single voxel.
+pub fn voxel_init(
+    input_path: &str,
+    augmentation: Augmentation,
+    periodic_input: bool,
+) -> Result<VoxelModel, Error> {
+    let grid = read_vox(input_path)?;
+
+    let mut ruleset = HashSet::<Rule<Voxel>>::new();
+    let mut frequencies = HashMap::<Voxel, u32>::new();
+    for depth in 0..grid.depth {
+        for height in 0..grid.height {
+            for width in 0..grid.width {
+                let from = grid
+                    .get(width, height, depth)
+                    .ok_or(Error::CoordinateOverflow)?;
+                let frequency = frequencies.entry(from).or_insert(0);
+                *frequency = frequency.checked_add(1).unwrap_or(u32::MAX);
+
+                for direction in Neighbourhood::Voxel.get_directions() {
+                    let (del_w, del_h) = direction.get_deltas();
+                    let (Some(new_w), Some(new_h), Some(new_d)) = (
+                        offset_in_sample(width, del_w, grid.width, periodic_input),
+                        offset_in_sample(height, del_h, grid.height, periodic_input),
+                        offset_in_sample(
+                            depth,
+                            direction.get_depth_delta(),
+                            grid.depth,
+                            periodic_input,
+                        ),
+                    ) else {
+                        continue;
+                    };
+
+                    let to = grid
+                        .get(new_w, new_h, new_d)
+                        .ok_or(Error::CoordinateOverflow)?;
+                    insert_symmetric_rules(
+                        &mut ruleset,
+                        from,
+                        to,
+                        direction,
+                        Neighbourhood::Voxel,
+                        augmentation,
+                    );
+                }
+            }
+        }
+    }
+
+    if ruleset.is_empty() {
+        return Err(Error::EmptyRuleset);
+    }
+
+    Ok(VoxelModel {
+        palette: grid.palette,
+        generation: Generation::new(&ruleset, &frequencies, Neighbourhood::Voxel),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // writes the grid to the temporary directory and reads it back
+    fn round_trip(name: &str, grid: &VoxelGrid) -> Result<VoxelGrid, Error> {
+        let path = env::temp_dir().join(format!("{name}-{}.vox", std::process::id()));
+        let path = path.to_string_lossy();
+        write_vox(grid, &path)?;
+        let read = read_vox(&path);
+        fs::remove_file(path.as_ref()).ok();
+        read
+    }
+
+    fn assert_same_grid(left: &VoxelGrid, right: &VoxelGrid) {
+        assert_eq!(
+            (left.width, left.height, left.depth),
+            (right.width, right.height, right.depth)
+        );
+        assert_eq!(left.voxels, right.voxels);
+        assert_eq!(left.palette, right.palette);
+    }
+
+    #[test]
+    fn round_trips_a_grid() -> Result<(), Error> {
+        let voxels = (0..24_u8)
+            .map(|colour_index| Voxel {
+                colour_index: colour_index % 5,
+            })
+            .collect();
+        let grid = VoxelGrid {
+            width: 4,
+            height: 3,
+            depth: 2,
+            voxels,
+            palette: None,
+        };
+
+        assert_same_grid(&round_trip("grid", &grid)?, &grid);
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_a_sample_and_its_palette() -> Result<(), Error> {
+        let grid = read_vox("resources/island.vox")?;
+        assert!(grid.palette.is_some());
+        assert!(grid.voxels.iter().any(|voxel| *voxel != EMPTY_VOXEL));
+
+        assert_same_grid(&round_trip("island", &grid)?, &grid);
+        Ok(())
+    }
+
+    #[test]
+    fn stores_rows_from_the_top_down() -> Result<(), Error> {
+        // a single voxel in the top row, which MagicaVoxel has as the highest z
+        let mut voxels = vec![EMPTY_VOXEL; 8];
+        if let Some(voxel) = voxels.first_mut() {
+            *voxel = Voxel { colour_index: 1 };
+        }
+        let grid = VoxelGrid {
+            width: 2,
+            height: 2,
+            depth: 2,
+            voxels,
+            palette: None,
+        };
+
+        let read = round_trip("top", &grid)?;
+        assert_eq!(read.get(0, 0, 0), Some(Voxel { colour_index: 1 }));
+        assert_eq!(read.get(0, 1, 0), Some(EMPTY_VOXEL));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_grids_too_large_for_the_format() {
+        let grid = VoxelGrid {
+            width: 257,
+            height: 1,
+            depth: 1,
+            voxels: vec![],
+            palette: None,
+        };
+        assert!(matches!(
+            round_trip("large", &grid),
+            Err(Error::CoordinateOverflow)
+        ));
+    }
+
+    #[test]
+    fn rejects_files_without_a_header() {
+        let path = env::temp_dir().join(format!("header-{}.vox", std::process::id()));
+        let path = path.to_string_lossy();
+        let written = fs::write(path.as_ref(), b"not a vox file");
+        let read = read_vox(&path);
+        fs::remove_file(path.as_ref()).ok();
+
+        assert!(written.is_ok());
+        assert!(matches!(read, Err(Error::InvalidVox { .. })));
+    }
+}
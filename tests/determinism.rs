@@ -0,0 +1,52 @@
+use wavefunction_collapse::{
+    generation_init, Augmentation, Board, Dimensions, Error, Neighbourhood, Solver, Tile,
+};
+
+const DIMENSIONS: Dimensions = Dimensions {
+    width: 24,
+    height: 16,
+    depth: 1,
+};
+
+// the tile index of every cell, or `None` for cells left hidden
+fn get_indices(board: &Board) -> Vec<Option<usize>> {
+    board
+        .cells()
+        .iter()
+        .map(|tile| match tile {
+            Tile::Revealed(index) => Some(*index),
+            Tile::Hidden(_) => None,
+        })
+        .collect()
+}
+
+// learns the rules anew for every run, so that the order tiles are indexed in is checked as well
+fn solve(seed: u64) -> Result<Vec<Option<usize>>, Error> {
+    let generation = generation_init(
+        "resources/beach.bmp",
+        Augmentation::ISOTROPIC,
+        false,
+        Neighbourhood::VonNeumann,
+    )?;
+    let board = Solver::new(&generation, DIMENSIONS)
+        .with_attempts(10)
+        .with_seed(seed)
+        .run()?;
+    Ok(get_indices(&board))
+}
+
+#[test]
+fn same_seed_gives_the_same_board() -> Result<(), Error> {
+    for seed in [0, 7, 12_345] {
+        let board = solve(seed)?;
+        assert!(board.iter().all(Option::is_some));
+        assert_eq!(board, solve(seed)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn different_seeds_give_different_boards() -> Result<(), Error> {
+    assert_ne!(solve(1)?, solve(2)?);
+    Ok(())
+}